@@ -0,0 +1,125 @@
+extern crate serde_json;
+
+use std::error::Error;
+use std::fs::File;
+use std::io::Write;
+
+use config;
+use export;
+use export::stl::{PrintParams, Solid};
+use world::maps::Maps;
+use world::mesh::Mesh;
+use world::stats::Stats;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Runs a headless subcommand, if one is given as the first argument.
+///
+/// Returns `None` if there's no subcommand, in which case the caller
+/// should open the viewer as usual.  The planet is picked with the same
+/// `--preset` and `--config` flags as the viewer.
+pub fn run(args : &[String]) -> Option<Result<(), Box<Error>>> {
+    let cmd = match args.get(1) {
+        Some(c) if !c.starts_with("--") => c,
+        _ => return None,
+    };
+    Some(match cmd.as_str() {
+        "stats" => stats(args),
+        "export" => export(args),
+        "stl" => stl(args),
+        "maps" => maps(args),
+        _ => Err(From::from(format!("Unknown command '{}'", cmd))),
+    })
+}
+
+/// Returns the value following a flag, e.g. `--out PATH`
+fn arg(args : &[String], flag : &str) -> Option<String> {
+    args.iter().position(|a| { a == flag })
+        .and_then(|i| { args.get(i + 1).cloned() })
+}
+
+/// Writes text to the `--out` path, or to stdout if none was given
+fn output(args : &[String], text : &str) -> Result<(), Box<Error>> {
+    match arg(args, "--out") {
+        Some(path) => File::create(path)?.write_all(text.as_bytes())?,
+        None => println!("{}", text),
+    }
+    Ok(())
+}
+
+/// `stats [--bins N] [--out PATH]`: prints a JSON report on the planet
+fn stats(args : &[String]) -> Result<(), Box<Error>> {
+    let bins = match arg(args, "--bins") {
+        Some(b) => b.parse()?,
+        None => 20,
+    };
+    let config = config::load();
+    let mesh = Mesh::generate(&config);
+    let stats = Stats::new(&mesh, &config.biomes, bins);
+    output(args, &serde_json::to_string_pretty(&stats)?)
+}
+
+/// `export PATH [--triangles N]`: writes the planet as OBJ, PLY, or
+/// glTF, depending on the file extension, optionally simplifying the
+/// terrain
+fn export(args : &[String]) -> Result<(), Box<Error>> {
+    let path = match args.get(2) {
+        Some(p) if !p.starts_with("--") => p,
+        _ => return Err(From::from("Usage: export PATH.{obj,ply,gltf,glb} [--triangles N]")),
+    };
+    let triangles = match arg(args, "--triangles") {
+        Some(n) => Some(n.parse()?),
+        None => None,
+    };
+    let config = config::load();
+    export::save(&export::parts(&config, triangles)?, path)
+}
+
+/// `stl PATH [--diameter MM] [--exaggeration K]`: writes a watertight,
+/// print-ready model of the planet, with oceans filled to sea level
+fn stl(args : &[String]) -> Result<(), Box<Error>> {
+    let path = match args.get(2) {
+        Some(p) if !p.starts_with("--") => p,
+        _ => return Err(From::from("Usage: stl PATH [--diameter MM] [--exaggeration K]")),
+    };
+    let mut params = PrintParams::default();
+    if let Some(d) = arg(args, "--diameter") {
+        params.diameter = d.parse()?;
+    }
+    if let Some(k) = arg(args, "--exaggeration") {
+        params.exaggeration = k.parse()?;
+    }
+
+    let config = config::load();
+    let mesh = Mesh::generate(&config);
+    let solid = Solid::new(&config, &mesh, &params);
+    solid.validate()?;
+    solid.save(path)
+}
+
+/// `maps PREFIX [--width N]`: writes equirectangular height, albedo,
+/// normal, and land mask images, named `PREFIXheight.png` and so on,
+/// plus plates, temperature, and precipitation if they're simulated
+fn maps(args : &[String]) -> Result<(), Box<Error>> {
+    let prefix = match args.get(2) {
+        Some(p) if !p.starts_with("--") => p,
+        _ => return Err(From::from("Usage: maps PREFIX [--width N]")),
+    };
+    let width = match arg(args, "--width") {
+        Some(w) => w.parse()?,
+        None => 2048,
+    };
+    if width < 2 {
+        return Err(From::from("Map width must be at least 2"));
+    }
+    let config = config::load();
+    let mesh = Mesh::generate(&config);
+    Maps::new(&config, &mesh, width).save(prefix)?;
+    if let Some(ref t) = mesh.tectonics {
+        t.save_plate_map(&mesh, &format!("{}plates.png", prefix), width)?;
+    }
+    if let Some(ref c) = mesh.climate {
+        c.save_maps(&mesh, prefix, width)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,275 @@
+extern crate serde_json;
+
+use std::env;
+use std::error::Error;
+use std::fs::File;
+use std::path::Path;
+
+use self::serde_json::Value;
+
+use presets;
+use world::biome::Biomes;
+use world::climate::ClimateParams;
+use world::craters::Craters;
+use world::elevation::Elevation;
+use world::erosion::Erosion;
+use world::gas_giant::GasGiant;
+use world::hydrology::HydrologyParams;
+use world::noise_graph::NoiseGraph;
+use world::orbit::{Orbit, Spin};
+use world::rings::Rings;
+use world::tectonics::TectonicParams;
+use world::noise_graph::NoiseGraph::{Perlin, Billow};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Controls how the terrain mesh is lit
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Shading {
+    /// One normal and color per triangle, for a faceted look
+    Flat,
+    /// Area-weighted vertex normals, interpolated across each triangle
+    Smooth,
+}
+
+/// Where the ocean surface sits, relative to the unit sphere
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SeaLevel {
+    /// A fixed height above the unit sphere
+    Height(f64),
+    /// Whatever height leaves this fraction (0 to 1) of the surface dry
+    LandFraction(f64),
+}
+
+/// A body orbiting a planet, with its own configuration
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Moon {
+    pub config : Config,
+    #[serde(default)]
+    pub orbit : Orbit,
+    /// Radius relative to the parent body
+    pub size : f64,
+}
+
+/// Parameters that control planet generation
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Added to the seed of every generator, so that one config can
+    /// produce a family of different planets
+    pub seed : u32,
+
+    /// Subdivision level of the terrain icosphere
+    pub level : u8,
+    pub shading : Shading,
+    /// If present, the terrain is simplified to about this many
+    /// triangles before drawing, for a low-poly look
+    pub low_poly : Option<usize>,
+    /// Height between contour lines in the overlay, in planet radii
+    pub contour_interval : f64,
+    pub elevation : Elevation,
+    /// Plate tectonics, blended with the elevation noise
+    pub tectonics : Option<TectonicParams>,
+    /// Impact craters, stamped before erosion
+    pub craters : Option<Craters>,
+    /// Erosion pass, run on heights before biomes are assigned
+    pub erosion : Option<Erosion>,
+    /// Lakes and rivers, computed from drainage after erosion
+    pub hydrology : Option<HydrologyParams>,
+    pub biomes : Biomes,
+    /// If present, biomes use simulated temperature and rainfall
+    /// instead of the noise-based moisture field
+    pub climate : Option<ClimateParams>,
+
+    /// If present, draw a gas giant instead of terrain, ocean, and clouds
+    pub gas_giant : Option<GasGiant>,
+    /// Planetary rings, for any kind of planet
+    pub rings : Option<Rings>,
+
+    /// Ocean color, or `None` for a dry planet
+    pub ocean : Option<[f32; 3]>,
+    /// Height of the ocean surface, which biome elevations, erosion,
+    /// climate, and rivers are all measured against
+    pub sea_level : SeaLevel,
+    /// Relative number of clouds; zero disables them
+    pub cloud_coverage : f64,
+    /// Atmosphere rim color, or `None` for an airless planet
+    pub atmosphere : Option<[f32; 3]>,
+    /// Brightness threshold for the star field; lower means more stars
+    pub star_cutoff : f32,
+
+    /// Rotation, axial tilt and seasons
+    pub spin : Spin,
+    /// Bodies orbiting this one
+    pub moons : Vec<Moon>,
+
+    /// Sparkle on the ocean surface
+    pub ocean_noise : NoiseGraph,
+    /// Texture for cloud puffs, sampled at pixel coordinates on a 64x64 grid
+    pub cloud_noise : NoiseGraph,
+    /// Background star field, sampled at pixel coordinates on a 512x512 grid
+    pub star_noise : NoiseGraph,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            seed : 0,
+            level : 5,
+            shading : Shading::Flat,
+            low_poly : None,
+            contour_interval : 0.01,
+            elevation : Elevation::default(),
+            tectonics : None,
+            craters : None,
+            erosion : None,
+            hydrology : None,
+            biomes : Biomes::default(),
+            climate : None,
+            gas_giant : None,
+            rings : None,
+            ocean : Some([0.1, 0.2, 0.5]),
+            sea_level : SeaLevel::Height(0.0),
+            cloud_coverage : 1.0,
+            atmosphere : Some([0.5, 0.8, 1.0]),
+            star_cutoff : 0.67,
+            spin : Spin::default(),
+            moons : Vec::new(),
+            ocean_noise : NoiseGraph::scaled(Perlin { seed : 0 }, 20.0),
+            cloud_noise : NoiseGraph::scaled(Billow(Default::default()), 0.1),
+            star_noise : NoiseGraph::scaled(Perlin { seed : 0 }, 0.2),
+        }
+    }
+}
+
+impl Config {
+    /// Returns a copy with `seed` folded into each generator's own seed
+    pub fn seeded(&self) -> Config {
+        let mut c = self.clone();
+        let s = self.seed;
+        c.elevation.seed = c.elevation.seed.wrapping_add(s);
+        c.tectonics.as_mut().map(|t| { t.seed = t.seed.wrapping_add(s) });
+        c.craters.as_mut().map(|t| { t.seed = t.seed.wrapping_add(s) });
+        c.erosion.as_mut().map(|t| { t.seed = t.seed.wrapping_add(s) });
+        c.elevation.graph.as_mut().map(|g| { g.reseed(s) });
+        c.biomes.moisture.reseed(s);
+        c.seed = 0;
+        c
+    }
+
+    /// Rough radius of everything drawn for this body, including its
+    /// rings and moons, relative to the planet's radius
+    pub fn extent(&self) -> f64 {
+        let relief = self.elevation.heightmap.as_ref()
+            .map(|h| { h.scale })
+            .unwrap_or(0.0)
+            .max(self.elevation.amplitude);
+        let own = (1.0 + relief)
+            .max(self.rings.as_ref().map(|r| { r.outer }).unwrap_or(0.0));
+        self.moons.iter()
+            .map(|m| { m.orbit.radius + m.size * m.config.extent() })
+            .fold(own, f64::max)
+    }
+
+    /// Checks for settings that would fail or misbehave during
+    /// generation, such as noise graphs that don't compile
+    pub fn validate(&self) -> Result<(), Box<Error>> {
+        let graphs = self.elevation.graph.iter()
+            .chain(Some(&self.biomes.moisture))
+            .chain(self.gas_giant.as_ref().map(|g| { &g.turbulence }))
+            .chain(self.rings.as_ref().map(|r| { &r.density }))
+            .chain(Some(&self.ocean_noise))
+            .chain(Some(&self.cloud_noise))
+            .chain(Some(&self.star_noise));
+        for g in graphs {
+            g.compile()?;
+        }
+        if self.biomes.table.is_empty() {
+            return Err(From::from("Biome table needs at least one entry"));
+        }
+        if !(self.contour_interval > 0.0) {
+            return Err(From::from("Contour interval must be positive"));
+        }
+        if self.spin.day == 0.0 || self.spin.year == 0.0 {
+            return Err(From::from("Spin day and year must be non-zero"));
+        }
+        if let Some(ref c) = self.craters {
+            if !(c.min_radius > 0.0) || !(c.exponent > 0.0) {
+                return Err(From::from("Crater min_radius and exponent must be positive"));
+            }
+            if !(c.min_radius <= c.max_radius) {
+                return Err(From::from("Crater min_radius must not exceed max_radius"));
+            }
+        }
+        if let Some(ref t) = self.tectonics {
+            if !(t.width > 0.0) {
+                return Err(From::from("Tectonic boundary width must be positive"));
+            }
+        }
+        for m in self.moons.iter() {
+            m.config.validate()?;
+        }
+        Ok(())
+    }
+
+    /// Builds and validates a configuration from parsed JSON.
+    /// Missing fields are filled in from the default configuration.
+    pub fn from_json(json : Value) -> Result<Config, Box<Error>> {
+        let config : Config = serde_json::from_value(json)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Reads a configuration from a JSON file.
+    /// Missing fields are filled in from the default configuration.
+    pub fn from_file<P : AsRef<Path>>(path : P) -> Result<Config, Box<Error>> {
+        let f = File::open(path)?;
+        Config::from_json(serde_json::from_reader(f)?)
+    }
+}
+
+/// Picks the starting configuration from the command line.
+///
+/// `--config PATH` loads a JSON file and `--preset NAME` selects a named
+/// preset; otherwise, `planet.json` is loaded from the working directory
+/// if it exists, falling back to the default configuration.
+pub fn load() -> Config {
+    let args : Vec<String> = env::args().collect();
+    let arg = |flag : &str| {
+        args.iter().position(|a| { a == flag })
+            .and_then(|i| { args.get(i + 1).cloned() })
+    };
+
+    if let Some(name) = arg("--preset") {
+        match presets::all().into_iter().find(|p| { p.0 == name }) {
+            Some((_, c)) => return c,
+            None => println!("Unknown preset '{}'", name),
+        }
+    }
+
+    let path = arg("--config").unwrap_or("planet.json".to_string());
+    if !Path::new(&path).exists() {
+        return Config::default();
+    }
+    Config::from_file(&path).unwrap_or_else(|err| {
+        println!("Couldn't load {}: {}", path, err);
+        Config::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bad_crater_radii_are_rejected() {
+        let with = |c : Craters| { Config { craters : Some(c), .. Config::default() } };
+        assert!(with(Craters::default()).validate().is_ok());
+        assert!(with(Craters { min_radius : 0.0, .. Craters::default() }).validate().is_err());
+        assert!(with(Craters { exponent : 0.0, .. Craters::default() }).validate().is_err());
+        assert!(with(Craters { min_radius : 0.5, max_radius : 0.1, .. Craters::default() })
+                    .validate().is_err());
+    }
+}
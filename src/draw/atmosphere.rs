@@ -1,11 +1,17 @@
+extern crate cgmath;
+
 use std::f32::consts::PI;
 use std::error::Error;
 
+use draw::layer::{Context, Layer};
+
 use glium::*;
 use glium::backend::Facade;
-use glium::uniforms::EmptyUniforms;
 use glium::index::{PrimitiveType, NoIndices};
 
+use self::cgmath::conv::*;
+use self::cgmath::InnerSpace;
+
 #[derive(Copy, Clone)]
 struct Vertex {
     position : [f32; 2],
@@ -15,19 +21,26 @@ implement_vertex!(Vertex, position);
 const VERTEX_SHADER_SRC : &'static str = r#"
 #version 410
 
+uniform vec3 center;
+uniform float radius;
+
 in vec2 position;
 out vec2 frag_pos;
 
 void main()
 {
+    // The glow is a screen-space disk placed just in front of the planet,
+    // so that it's hidden by anything closer (but not by its own planet)
     frag_pos = position;
-    gl_Position = vec4(position * 0.75, 0.0, 1.0);
+    gl_Position = vec4(center.xy + position * radius, center.z - radius, 1.0);
 }
 "#;
 
 const FRAGMENT_SHADER_SRC : &'static str = r#"
 #version 410
 
+uniform vec3 color;
+
 in vec2 frag_pos;
 out vec4 out_color;
 
@@ -41,17 +54,18 @@ void main()
         a = exp(-pow(r - cut, 2) / 0.001);
     else
         a = exp(-pow(r - cut, 2) / 0.01);
-    out_color = vec4(0.5f, 0.8f, 1.0f, a/5);
+    out_color = vec4(color, a/5);
 }
 "#;
 
 pub struct Atmosphere {
     vbo : VertexBuffer<Vertex>,
     program : Program,
+    color : [f32; 3],
 }
 
 impl Atmosphere {
-    pub fn new<F>(facade : &F) -> Result<Atmosphere, Box<Error>>
+    pub fn new<F>(facade : &F, color : [f32; 3]) -> Result<Atmosphere, Box<Error>>
         where F : Facade
     {
         let mut buffer : Vec<Vertex> = Vec::new();
@@ -68,15 +82,31 @@ impl Atmosphere {
         let v = VertexBuffer::new(facade, &buffer)?;
         let p = Program::from_source(facade, VERTEX_SHADER_SRC,
                                      FRAGMENT_SHADER_SRC, None)?;
-        Ok(Atmosphere { vbo : v, program : p })
+        Ok(Atmosphere { vbo : v, program : p, color : color })
     }
+}
 
-    pub fn draw(&self, frame : &mut Frame, params : &DrawParameters) {
+impl Layer for Atmosphere {
+    fn draw(&self, ctx : &Context, frame : &mut Frame) {
         let params = DrawParameters {
+            depth : Depth {
+                test: DepthTest::IfLess,
+                write: false,
+                .. Default::default()
+            },
             blend : draw_parameters::Blend::alpha_blending(),
-            .. params.clone()
+            .. ctx.params.clone()
         };
         let indices = NoIndices(PrimitiveType::TriangleFan);
-        frame.draw(&self.vbo, indices, &self.program, &EmptyUniforms, &params).unwrap();
+
+        // The glow's radius was tuned for a planet of radius 0.7
+        let center = ctx.model.w.truncate();
+        let scale = ctx.model.x.truncate().magnitude();
+        let uniforms = uniform! {
+            color : self.color,
+            center : array3(center),
+            radius : scale * 0.75 / 0.7,
+        };
+        frame.draw(&self.vbo, indices, &self.program, &uniforms, &params).unwrap();
     }
 }
@@ -1,20 +1,21 @@
 extern crate image;
-extern crate noise;
 extern crate rand;
 extern crate cgmath;
 
 use std::error::Error;
 
+use config::Config;
+use draw::debug::{DebugView, wireframe, with_debug};
+use draw::layer::{Context, Layer};
+
 use glium::*;
 use glium::backend::Facade;
-use glium::uniforms::EmptyUniforms;
 use glium::index::{PrimitiveType, NoIndices};
 use glium::texture::{RawImage2d, Texture2d};
 
-use self::image::{GenericImage, ImageBuffer, ConvertBuffer, RgbImage};
-use self::noise::NoiseFn;
+use self::image::ImageBuffer;
 
-use self::cgmath::{Matrix4, Vector3, InnerSpace};
+use self::cgmath::{Vector3, InnerSpace};
 use self::cgmath::conv::{array3, array4x4};
 
 use self::rand::distributions::{Range, Sample};
@@ -32,6 +33,7 @@ const VERTEX_SHADER_SRC : &'static str = r#"
 #version 410
 
 uniform mat4 M;
+uniform vec3 sun;
 
 in vec3 position;
 in vec2 offset;
@@ -40,13 +42,20 @@ in int index;
 out vec2 tex_coord;
 out float tex_index;
 out float depth;
+out float light;
 
 void main() {
+    // Billboards and depth fading are relative to the body's size and
+    // center, which was originally a unit sphere scaled by 0.7
+    float scale = length(M[0].xyz) / 0.7;
+    vec4 center = M * vec4(0.0, 0.0, 0.0, 1.0);
+
     vec4 pos = M * vec4(position, 1.0);
-    gl_Position = pos + vec4(offset / 20.0, 0.0, 0.0);
+    gl_Position = pos + vec4(offset * scale / 20.0, 0.0, 0.0);
     tex_coord = offset / 2.0 + 0.5;
-    depth = pos.z;
+    depth = (pos.z - center.z) / scale;
     tex_index = index;
+    light = 0.2 + 0.8 * max(dot(normalize(pos.xyz - center.xyz), normalize(sun)), 0.0);
 }
 "#;
 
@@ -56,6 +65,7 @@ const FRAGMENT_SHADER_SRC : &'static str = r#"
 in vec2 tex_coord;
 in float depth;
 in float tex_index;
+in float light;
 
 uniform sampler2D tex;
 
@@ -63,6 +73,15 @@ out vec4 color_out;
 
 void main()
 {
+    // Outline the edges of each billboard, about a pixel wide
+    if (debug_view == DEBUG_CLOUDS) {
+        vec2 edge = min(tex_coord, 1.0 - tex_coord) / max(fwidth(tex_coord), vec2(1e-4));
+        if (min(edge.x, edge.y) < 1.0) {
+            color_out = vec4(1.0, 0.2, 0.8, 1.0);
+            return;
+        }
+    }
+
     float shade = 1.0;
 
     // Blend clouds as they go behind the planet, since otherwise
@@ -84,7 +103,7 @@ void main()
     }
 
     float r = texture(tex, (tex_coord + vec2(mod(tex_index, 6.0), mod(tex_index, 36.0))) / 6.0).r * shade;
-    color_out = vec4(1.0, 1.0, 1.0, r);
+    color_out = vec4(vec3(light), r);
 }
 "#;
 
@@ -94,50 +113,62 @@ pub struct Clouds {
     tex: Texture2d,
 }
 
+/// Half-width of a cloud billboard, in planet radii
+/// (matching the vertex shader's offset for a planet scaled by 0.7)
+pub const PUFF_RADIUS : f32 = 0.05 / 0.7;
+
+/// Returns the center of every cloud puff, in clusters of 25
+pub fn puffs(config : &Config) -> Vec<Vector3<f32>> {
+    let mut jitter = {
+        let seed: &[_] = &[0];
+        let mut rng : ChaChaRng = SeedableRng::from_seed(seed);
+        let mut between = Range::new(-1.0, 1.0);
+        move || { between.sample(&mut rng) }
+    };
+    let mut out = Vec::new();
+    for _ in 0..(100.0 * config.cloud_coverage) as usize {
+        // Pick a central seed for the cloud on the unit sphere
+        let mut v = Vector3::new(1.0, 1.0, 1.0);
+        while v.magnitude() > 1.0 {
+            v = Vector3::new(jitter(), jitter(), jitter());
+        }
+
+        v = v.normalize() * 1.1;
+        for _ in 0..25 {
+            let w = v + Vector3::new(jitter(), jitter(), jitter()) / 10.0;
+
+            // Prevent the clouds from drifting too much on the Z axis
+            let m = (w.magnitude() - 1.0) / 10.0  + 1.0;
+            out.push(w * m / w.magnitude() * v.magnitude());
+        }
+    }
+    out
+}
+
 impl Clouds {
-    pub fn new<F>(facade : &F) -> Result<Clouds, Box<Error>>
+    pub fn new<F>(facade : &F, config : &Config) -> Result<Clouds, Box<Error>>
         where F : Facade
     {
-        let mut jitter = {
-            let seed: &[_] = &[0];
-            let mut rng : ChaChaRng = SeedableRng::from_seed(seed);
-            let mut between = Range::new(-1.0, 1.0);
-            move || { between.sample(&mut rng) }
-        };
         let mut verts : Vec<Vertex> = Vec::new();
         let mut index = 0;
-        for i in 0..100 {
-            // Pick a central seed for the cloud on the unit sphere
-            let mut v = Vector3::new(1.0, 1.0, 1.0);
-            while v.magnitude() > 1.0 {
-                v = Vector3::new(jitter(), jitter(), jitter());
-            }
-
-            v = v.normalize() * 1.1;
-            for j in 0..25 {
-                let w = v + Vector3::new(jitter(), jitter(), jitter()) / 10.0;
-
-                // Prevent the clouds from drifting too much on the Z axis
-                let m = (w.magnitude() - 1.0) / 10.0  + 1.0;
-                let w = array3(w * m / w.magnitude() * v.magnitude());
-
-                verts.push(Vertex {  position: w, offset: [-1f32, -1f32], index: index });
-                verts.push(Vertex {  position: w, offset: [ 1f32, -1f32], index: index });
-                verts.push(Vertex {  position: w, offset: [ 1f32,  1f32], index: index });
-
-                verts.push(Vertex {  position: w, offset: [-1f32, -1f32], index: index });
-                verts.push(Vertex {  position: w, offset: [ 1f32,  1f32], index: index });
-                verts.push(Vertex {  position: w, offset: [-1f32,  1f32], index: index });
-
-                index += 1;
-            }
+        for p in puffs(config) {
+            let w = array3(p);
+
+            verts.push(Vertex {  position: w, offset: [-1f32, -1f32], index: index });
+            verts.push(Vertex {  position: w, offset: [ 1f32, -1f32], index: index });
+            verts.push(Vertex {  position: w, offset: [ 1f32,  1f32], index: index });
+
+            verts.push(Vertex {  position: w, offset: [-1f32, -1f32], index: index });
+            verts.push(Vertex {  position: w, offset: [ 1f32,  1f32], index: index });
+            verts.push(Vertex {  position: w, offset: [-1f32,  1f32], index: index });
+
+            index += 1;
         }
 
         // Build a billowy noise texture; different quads index into
         // different regions on the texture to hide repetition.
         let img = {
-            let per = noise::ScalePoint::new(noise::Billow::new())
-                .set_all_scales(0.1, 0.1, 0.1, 1.0);
+            let per = config.cloud_noise.compile()?;
             ImageBuffer::from_fn(64, 64, |x, y| {
                 let a = per.get([x as f64, y as f64, 0.0]);
                 let a = ((a/2.0 + 0.5) * 255.0) as u8;
@@ -150,24 +181,30 @@ impl Clouds {
 
         let v = VertexBuffer::new(facade, &verts)?;
         let p = Program::from_source(facade, VERTEX_SHADER_SRC,
-                                     FRAGMENT_SHADER_SRC, None)?;
+                                     &with_debug(FRAGMENT_SHADER_SRC), None)?;
         Ok(Clouds{ vbo: v, program: p, tex: tex })
     }
+}
 
-    pub fn draw(&self, mat : Matrix4<f32>, frame : &mut Frame, params : &DrawParameters) {
-        let params = DrawParameters {
+impl Layer for Clouds {
+    fn draw(&self, ctx : &Context, frame : &mut Frame) {
+        if DebugView::surface_only(ctx.debug) {
+            return;
+        }
+        let params = wireframe(ctx.debug, DrawParameters {
             depth : Depth {
                 test: DepthTest::IfLess,
                 write: false,
                 .. Default::default()
             },
             blend : draw_parameters::Blend::alpha_blending(),
-            .. params.clone()
-        };
-        let indices = NoIndices(PrimitiveType::TrianglesList);
+            .. ctx.params.clone()
+        });
         let uniforms = uniform! {
-            M : array4x4(mat),
+            M : array4x4(ctx.mat),
             tex : &self.tex,
+            sun : ctx.sun,
+            debug_view : DebugView::uniform(ctx.debug),
         };
         let indices = NoIndices(PrimitiveType::TrianglesList);
 
@@ -0,0 +1,113 @@
+use draw::util::after_version;
+
+use glium::{DrawParameters, PolygonMode};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Alternate ways of drawing the planet, for inspecting its layers
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DebugView {
+    /// Triangle edges only
+    Wireframe,
+    /// Face normals, in the body's frame, as RGB
+    Normals,
+    /// Height above sea level in false color
+    Elevation,
+    /// A distinct color for each biome
+    Biome,
+    Moisture,
+    /// Annual mean temperature, from blue to red
+    Temperature,
+    /// Outlines of each cloud billboard
+    Clouds,
+}
+
+impl DebugView {
+    /// Returns the next view, or `None` to return to normal drawing
+    pub fn next(v : Option<DebugView>) -> Option<DebugView> {
+        match v {
+            None => Some(DebugView::Wireframe),
+            Some(DebugView::Wireframe) => Some(DebugView::Normals),
+            Some(DebugView::Normals) => Some(DebugView::Elevation),
+            Some(DebugView::Elevation) => Some(DebugView::Biome),
+            Some(DebugView::Biome) => Some(DebugView::Moisture),
+            Some(DebugView::Moisture) => Some(DebugView::Temperature),
+            Some(DebugView::Temperature) => Some(DebugView::Clouds),
+            Some(DebugView::Clouds) => None,
+        }
+    }
+
+    /// Value of the `debug_view` uniform, matching the `DEBUG_*`
+    /// constants in the shaders (0 for normal drawing)
+    pub fn uniform(v : Option<DebugView>) -> i32 {
+        match v {
+            None => 0,
+            Some(DebugView::Wireframe) => 1,
+            Some(DebugView::Normals) => 2,
+            Some(DebugView::Elevation) => 3,
+            Some(DebugView::Biome) => 4,
+            Some(DebugView::Moisture) => 5,
+            Some(DebugView::Temperature) => 6,
+            Some(DebugView::Clouds) => 7,
+        }
+    }
+
+    /// Whether water and clouds are hidden, so that the terrain's
+    /// data shows everywhere
+    pub fn surface_only(v : Option<DebugView>) -> bool {
+        match v {
+            Some(DebugView::Elevation) | Some(DebugView::Biome) |
+            Some(DebugView::Moisture) | Some(DebugView::Temperature) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Switches to drawing triangle edges in the wireframe view
+pub fn wireframe<'a>(v : Option<DebugView>, params : DrawParameters<'a>)
+    -> DrawParameters<'a>
+{
+    if v == Some(DebugView::Wireframe) {
+        DrawParameters { polygon_mode : PolygonMode::Line, .. params }
+    } else {
+        params
+    }
+}
+
+const DEBUG_SRC : &'static str = r#"
+#define DEBUG_WIREFRAME 1
+#define DEBUG_NORMALS 2
+#define DEBUG_ELEVATION 3
+#define DEBUG_BIOME 4
+#define DEBUG_MOISTURE 5
+#define DEBUG_TEMPERATURE 6
+#define DEBUG_CLOUDS 7
+
+uniform int debug_view;
+
+// Shades of blue below zero, then green, yellow, brown, and white up to one
+vec3 false_color(float t)
+{
+    if (t < 0.0) {
+        return mix(vec3(0.6, 0.8, 1.0), vec3(0.0, 0.0, 0.4), clamp(-t, 0.0, 1.0));
+    }
+    t = clamp(t, 0.0, 1.0) * 3.0;
+    if (t < 1.0) {
+        return mix(vec3(0.1, 0.5, 0.1), vec3(0.9, 0.9, 0.2), t);
+    } else if (t < 2.0) {
+        return mix(vec3(0.9, 0.9, 0.2), vec3(0.5, 0.3, 0.1), t - 1.0);
+    }
+    return mix(vec3(0.5, 0.3, 0.1), vec3(1.0), t - 2.0);
+}
+
+// A distinct color for each integer id
+vec3 id_color(float id)
+{
+    return 0.3 + 0.7 * fract(id * vec3(0.618034, 0.414214, 0.732051) + vec3(0.1, 0.5, 0.3));
+}
+"#;
+
+/// Adds the debug view constants and functions to a shader
+pub fn with_debug(src : &str) -> String {
+    after_version(src, DEBUG_SRC)
+}
@@ -1,28 +1,213 @@
 extern crate cgmath;
 extern crate glium;
 
+use config;
+use config::{Config, Shading};
+use presets;
+use draw::debug::DebugView;
+use draw::layer::{Context, Layer};
+use draw::map::{MapView, Projection};
+use draw::overlay::Overlays;
 use draw::planet::Planet;
+use draw::scene::Scene;
 use draw::stars::Stars;
+use draw::util::sun;
 
 use glium::*;
 
-use self::cgmath::{Vector4, Matrix4, Rad, SquareMatrix, Transform, Array};
+use self::cgmath::{Matrix4, SquareMatrix};
 
 ////////////////////////////////////////////////////////////////////////////////
 
 pub struct State
 {
-    planet : Option<Planet>,
+    display : glium::Display,
+    config : Config,
+    scene : Option<Scene>,
     stars : Option<Stars>,
+
+    /// Flat map, built the first time it's shown
+    map : Option<MapView>,
+    /// Active map projection, or `None` to show the globe
+    projection : Option<Projection>,
+    graticule : bool,
+    /// Lines drawn over the globe
+    overlays : Overlays,
+    /// Active debug view, or `None` for normal drawing
+    debug : Option<DebugView>,
+
+    /// Index of the active preset, once one has been picked with a key
+    preset : Option<usize>,
+
+    /// Planet whose layers the keys act on, counting the root as 0 and
+    /// then its moons depth-first
+    body : usize,
+    /// Layer most recently toggled, which can then be moved or removed
+    layer : Option<String>,
 }
 
 impl State {
     pub fn new(display : &glium::Display) -> State {
-        State {
-            planet : Planet::new(display).map_err(|err| {
-                println!("Couldn't construct Planet: {}", err)}).ok(),
-            stars : Stars::new(display).map_err(|err| {
-                println!("Couldn't construct Stars: {}", err)}).ok(),
+        let mut state = State { display : display.clone(), config : Config::default(),
+                                scene : None, stars : None, map : None,
+                                projection : None, graticule : true,
+                                overlays : Overlays::default(), debug : None,
+                                preset : None, body : 0, layer : None };
+        state.build(&config::load());
+        state
+    }
+
+    /// Rebuilds the planets and star field from a new config
+    fn build(&mut self, config : &Config) {
+        self.scene = Some(Scene::new(&self.display, config));
+        self.stars = Stars::new(&self.display, config).map_err(|err| {
+            println!("Couldn't construct Stars: {}", err)}).ok();
+        self.body = 0;
+        self.layer = None;
+        self.rebuild(config);
+    }
+
+    /// Reloads the config that's showing and swaps the new layers in,
+    /// keeping the selected planet and each layer's order and visibility
+    fn reload(&mut self) {
+        let config = match self.preset {
+            Some(i) => presets::all().swap_remove(i).1,
+            None => config::load(),
+        };
+        match self.scene {
+            Some(ref mut s) => s.reload(&self.display, &config),
+            None => {
+                self.build(&config);
+                return;
+            },
+        }
+        self.stars = Stars::new(&self.display, &config).map_err(|err| {
+            println!("Couldn't construct Stars: {}", err)}).ok();
+        self.rebuild(&config);
+    }
+
+    /// Stores a new config and rebuilds the map from it if it's showing
+    fn rebuild(&mut self, config : &Config) {
+        self.config = config.clone();
+        self.map = None;
+        if self.projection.is_some() {
+            self.build_map();
+        }
+    }
+
+    fn build_map(&mut self) {
+        let mesh = self.scene.as_ref().and_then(|s| { s.mesh() });
+        self.map = MapView::new(&self.display, &self.config, mesh).map_err(|err| {
+            println!("Couldn't construct MapView: {}", err)}).ok();
+    }
+
+    /// The selected planet, if it still exists
+    fn planet(&mut self) -> Option<&mut Planet> {
+        let body = self.body;
+        self.scene.as_mut().and_then(|s| { s.planets_mut().into_iter().nth(body) })
+    }
+
+    /// Moves the selected layer by `step` places in the draw order
+    fn shift(&mut self, step : isize) {
+        if let Some(name) = self.layer.clone() {
+            if let Some(p) = self.planet() {
+                let index = p.layers.names().iter().position(|&(n, _)| { n == name });
+                if let Some(i) = index {
+                    let i = (i as isize + step).max(0) as usize;
+                    p.layers.move_to(&name, i);
+                    println!("Layers: {:?}", p.layers.names());
+                }
+            }
+        }
+    }
+
+    pub fn key(&mut self, c : char) {
+        match c {
+            // Switch between flat and smooth shading
+            's' => {
+                let mut config = self.config.clone();
+                config.shading = match config.shading {
+                    Shading::Flat => Shading::Smooth,
+                    Shading::Smooth => Shading::Flat,
+                };
+                println!("Shading: {:?}", config.shading);
+                self.build(&config);
+            },
+            // Cycle through presets
+            'p' => {
+                let presets = presets::all();
+                let i = self.preset.map(|i| { (i + 1) % presets.len() })
+                                   .unwrap_or(0);
+                println!("Switching to preset '{}'", presets[i].0);
+                self.build(&presets[i].1);
+                self.preset = Some(i);
+            },
+            // Cycle between the globe and map projections
+            'm' => {
+                self.projection = Projection::next(self.projection);
+                if self.projection.is_some() && self.map.is_none() {
+                    self.build_map();
+                }
+            },
+            // Toggle overlays, with the graticule on whichever view is showing
+            'g' => if self.projection.is_some() {
+                self.graticule = !self.graticule;
+            } else {
+                self.overlays.graticule = !self.overlays.graticule;
+            },
+            'c' => self.overlays.contours = !self.overlays.contours,
+            't' => self.overlays.tropics = !self.overlays.tropics,
+            // Cycle through debug views
+            'v' => {
+                self.debug = DebugView::next(self.debug);
+                match self.debug {
+                    Some(v) => println!("Debug view: {:?}", v),
+                    None => println!("Debug view off"),
+                }
+            },
+            // Cycle through the planet and its moons for the keys below
+            'b' => {
+                let count = self.scene.as_mut().map(|s| { s.planets_mut().len() })
+                                               .unwrap_or(0);
+                self.body = (self.body + 1) % count.max(1);
+                self.layer = None;
+                let body = self.body;
+                if let Some(p) = self.planet() {
+                    println!("Body {}: {:?}", body, p.layers.names());
+                }
+            },
+            // Show or hide the selected planet's layers, by position
+            c if c >= '1' && c <= '9' => {
+                let i = c as usize - '1' as usize;
+                let mut selected = None;
+                if let Some(p) = self.planet() {
+                    let layer = p.layers.names().get(i)
+                        .map(|&(name, on)| { (name.to_string(), on) });
+                    if let Some((name, on)) = layer {
+                        p.layers.set_enabled(&name, !on);
+                        println!("{} {}", name, if on { "hidden" } else { "shown" });
+                        selected = Some(name);
+                    }
+                }
+                self.layer = selected;
+            },
+            // Draw the selected layer earlier or later
+            '[' => self.shift(-1),
+            ']' => self.shift(1),
+            // Remove the selected layer until the next reload
+            'x' => if let Some(name) = self.layer.take() {
+                if let Some(p) = self.planet() {
+                    if p.layers.remove(&name).is_some() {
+                        println!("Removed {}", name);
+                    }
+                }
+            },
+            // Reload the config and swap in the rebuilt layers
+            'r' => {
+                println!("Reloading");
+                self.reload();
+            },
+            _ => (),
         }
     }
 
@@ -37,7 +222,18 @@ impl State {
             .. Default::default()
         };
 
-        self.stars.as_ref().map(|a| { a.draw(frame, &params) });
-        self.planet.as_ref().map(|p| { p.draw(counter, frame, &params) });
+        let ctx = Context {
+            model : Matrix4::identity(), mat : Matrix4::identity(),
+            axis : Matrix4::identity(),
+            counter : counter, sun : sun(),
+            overlays : self.overlays, debug : self.debug,
+            params : &params,
+        };
+
+        self.stars.as_ref().map(|a| { a.draw(&ctx, frame) });
+        match (self.projection, self.map.as_ref()) {
+            (Some(p), Some(m)) => m.draw(p, self.graticule, counter, frame, &params),
+            _ => { self.scene.as_ref().map(|s| { s.draw(&ctx, frame) }); },
+        }
     }
 }
@@ -0,0 +1,152 @@
+extern crate cgmath;
+
+use std::error::Error;
+
+use draw::icosphere::icosphere;
+use draw::layer::{Context, Layer};
+use world::gas_giant::GasGiant;
+
+use glium::*;
+use glium::backend::Facade;
+use glium::index::PrimitiveType;
+use glium::texture::{RawImage2d, Texture2d, MipmapsOption};
+use glium::uniforms::SamplerWrapFunction;
+
+use self::cgmath::conv::*;
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Copy, Clone)]
+struct Vertex {
+    position : [f32; 3],
+}
+implement_vertex!(Vertex, position);
+
+const VERTEX_SHADER_SRC : &'static str = r#"
+#version 410
+
+uniform mat4 M;
+
+in vec3 position;
+
+out vec3 frag_position;
+out vec3 frag_normal;
+
+void main() {
+    gl_Position = M * vec4(position, 1.0);
+    frag_position = position;
+    frag_normal = normalize((M * vec4(position, 0.0)).xyz);
+}
+"#;
+
+const FRAGMENT_SHADER_SRC : &'static str = r#"
+#version 410
+
+#define PI 3.1415926535897932384626433832795
+
+// Furthest that bands slip apart, in texture widths, before fading back
+#define MAX_SLIP 0.05
+
+uniform sampler2D tex;
+uniform float time;
+uniform float shear;
+uniform float bands;
+uniform float limb_darkening;
+uniform vec3 sun;
+
+in vec3 frag_position;
+in vec3 frag_normal;
+
+out vec4 color_out;
+
+void main()
+{
+    vec3 p = normalize(frag_position);
+    float lat = asin(p.y);
+    float lon = atan(p.z, p.x);
+
+    // Neighboring bands drift in opposite directions.  Letting the offset
+    // grow forever would smear the texture into stripes, so blend two
+    // copies whose drift restarts half a cycle apart, each fading out
+    // just before it jumps back.
+    float drift = shear * sin(lat * bands);
+    float cycle = MAX_SLIP / max(abs(shear), 1e-6);
+    float phase = fract(time / cycle);
+    float u = (lon + PI) / (2 * PI);
+    float v = 0.5 - lat / PI;
+    vec3 a = texture(tex, vec2(u + drift * cycle * phase, v)).rgb;
+    vec3 b = texture(tex, vec2(u + drift * cycle * fract(phase + 0.5), v)).rgb;
+    vec3 color = mix(a, b, abs(1.0 - 2.0 * phase));
+
+    // The viewer looks down +Z, so facing surfaces have negative Z normals
+    float mu = max(-frag_normal.z, 0.0);
+    float day = 0.1 + 0.9 * max(dot(normalize(frag_normal), normalize(sun)), 0.0);
+    color_out = vec4(color * pow(mu, limb_darkening) * day, 1.0);
+}
+"#;
+
+pub struct GasGiantLayer {
+    vbo : VertexBuffer<Vertex>,
+    indices : IndexBuffer<u32>,
+    program : Program,
+    tex : Texture2d,
+    params : GasGiant,
+}
+
+impl GasGiantLayer {
+    pub fn new<F>(facade : &F, params : &GasGiant) -> Result<GasGiantLayer, Box<Error>>
+        where F : Facade
+    {
+        let (v, i) = icosphere(5);
+        let buffer : Vec<Vertex> = v.iter().map(|v| {
+            Vertex { position : [v[0] as f32, v[1] as f32, v[2] as f32] }
+        }).collect();
+
+        let mut indices : Vec<u32> = Vec::new();
+        i.iter().for_each(|i| {
+            indices.push(i.x as u32);
+            indices.push(i.y as u32);
+            indices.push(i.z as u32);
+        });
+
+        // Skip mipmaps, which would leave a seam where longitude wraps
+        let img = params.texture(1024, 512)?;
+        let image_dimensions = img.dimensions();
+        let img = RawImage2d::from_raw_rgb(img.into_raw(), image_dimensions);
+        let tex = Texture2d::with_mipmaps(facade, img, MipmapsOption::NoMipmap)?;
+
+        let v = VertexBuffer::new(facade, &buffer)?;
+        let i = IndexBuffer::new(facade, PrimitiveType::TrianglesList,
+                                 &indices)?;
+        let p = Program::from_source(facade, VERTEX_SHADER_SRC,
+                                     FRAGMENT_SHADER_SRC, None)?;
+        Ok(GasGiantLayer { vbo : v, indices : i, program : p, tex : tex,
+                           params : params.clone() })
+    }
+}
+
+impl Layer for GasGiantLayer {
+    fn draw(&self, ctx : &Context, frame : &mut Frame) {
+        let params = DrawParameters {
+            depth : Depth {
+                test: DepthTest::IfLess,
+                write: true,
+                .. Default::default()
+            },
+            .. ctx.params.clone()
+        };
+
+        let uniforms = uniform! {
+            M : array4x4(ctx.mat),
+            tex : self.tex.sampled().wrap_function(SamplerWrapFunction::Repeat),
+            time : ctx.counter as f32,
+            shear : self.params.shear,
+            bands : self.params.bands as f32,
+            limb_darkening : self.params.limb_darkening,
+            sun : ctx.sun,
+        };
+
+        frame.draw(&self.vbo, &self.indices, &self.program,
+                   &uniforms, &params).unwrap();
+    }
+}
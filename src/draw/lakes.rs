@@ -0,0 +1,119 @@
+extern crate cgmath;
+
+use std::error::Error;
+
+use draw::debug::{DebugView, wireframe};
+use draw::layer::{Context, Layer};
+use world::hydrology::Hydrology;
+use world::mesh::Mesh;
+
+use glium::*;
+use glium::backend::Facade;
+use glium::index::{PrimitiveType, NoIndices};
+
+use self::cgmath::conv::*;
+use self::cgmath::InnerSpace;
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Copy, Clone)]
+struct Vertex {
+    position : [f32; 3],
+}
+implement_vertex!(Vertex, position);
+
+const VERTEX_SHADER_SRC : &'static str = r#"
+#version 410
+
+uniform mat4 M;
+
+in vec3 position;
+
+out vec3 frag_normal;
+
+void main() {
+    gl_Position = M * vec4(position, 1.0);
+    frag_normal = normalize((M * vec4(position, 0.0)).xyz);
+}
+"#;
+
+const FRAGMENT_SHADER_SRC : &'static str = r#"
+#version 410
+
+uniform vec3 sun;
+
+in vec3 frag_normal;
+
+out vec4 color_out;
+
+void main()
+{
+    float shade = max(dot(frag_normal, normalize(sun)), 0.0) * 0.8;
+    vec3 blue = vec3(0.15, 0.3, 0.5) * shade + vec3(0.02, 0.08, 0.2) * (1 - shade);
+    color_out = vec4(blue, 1.0f);
+}
+"#;
+
+pub struct Lakes {
+    vbo : VertexBuffer<Vertex>,
+    program : Program,
+}
+
+impl Lakes {
+    pub fn new<F>(facade : &F, mesh : &Mesh, hydrology : &Hydrology)
+        -> Result<Lakes, Box<Error>>
+        where F : Facade
+    {
+        // Draw a water surface over every triangle that touches a lake;
+        // the surrounding terrain pokes through it at the shore.  Each
+        // corner is lifted from the terrain vertex itself, rather than
+        // from the sphere, so the jitter can't push the lake bed through
+        // the water, and a small bias keeps shallow water from z-fighting.
+        let mut buffer : Vec<Vertex> = Vec::new();
+        for t in mesh.tris.iter() {
+            let level = (0..3).filter_map(|j| { hydrology.lakes[t[j]] })
+                              .fold(None, |a : Option<f64>, b| {
+                                  Some(a.map_or(b, |a| { a.max(b) })) });
+            if let Some(level) = level {
+                for j in 0..3 {
+                    let v = mesh.verts[t[j]];
+                    let p = v + v.normalize() * (level - mesh.heights[t[j]] + 5e-4);
+                    buffer.push(Vertex { position : [p.x as f32, p.y as f32, p.z as f32] });
+                }
+            }
+        }
+
+        let v = VertexBuffer::new(facade, &buffer)?;
+        let p = Program::from_source(facade, VERTEX_SHADER_SRC,
+                                     FRAGMENT_SHADER_SRC, None)?;
+        Ok(Lakes { vbo : v, program : p })
+    }
+}
+
+impl Layer for Lakes {
+    /// Draws the lake surfaces, which have no data for the debug views
+    /// other than the wireframe, so they're hidden in the rest
+    fn draw(&self, ctx : &Context, frame : &mut Frame) {
+        if ctx.debug.is_some() && ctx.debug != Some(DebugView::Wireframe) {
+            return;
+        }
+
+        let params = wireframe(ctx.debug, DrawParameters {
+            depth : Depth {
+                test: DepthTest::IfLess,
+                write: true,
+                .. Default::default()
+            },
+            .. ctx.params.clone()
+        });
+
+        let indices = NoIndices(PrimitiveType::TrianglesList);
+        let uniforms = uniform! {
+            M : array4x4(ctx.mat),
+            sun : ctx.sun,
+        };
+
+        frame.draw(&self.vbo, indices, &self.program,
+                   &uniforms, &params).unwrap();
+    }
+}
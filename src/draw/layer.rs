@@ -0,0 +1,33 @@
+extern crate cgmath;
+
+use draw::debug::DebugView;
+use draw::overlay::Overlays;
+
+use glium::{DrawParameters, Frame};
+
+use self::cgmath::Matrix4;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Everything a layer needs to draw itself
+#[derive(Copy, Clone)]
+pub struct Context<'a> {
+    /// Places the body in the scene, scaled to its size
+    pub model : Matrix4<f32>,
+    /// `model` turned by the body's spin, for layers fixed to its surface
+    pub mat : Matrix4<f32>,
+    /// `model` leaned by the body's axial tilt, without its daily turn
+    pub axis : Matrix4<f32>,
+    /// Animation time, in frames
+    pub counter : i32,
+    /// Direction towards the star, as a shader uniform
+    pub sun : [f32; 3],
+    pub overlays : Overlays,
+    pub debug : Option<DebugView>,
+    pub params : &'a DrawParameters<'a>,
+}
+
+/// Something that can be drawn as part of a planet or the sky
+pub trait Layer {
+    fn draw(&self, ctx : &Context, frame : &mut Frame);
+}
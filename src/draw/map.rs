@@ -0,0 +1,178 @@
+use std::error::Error;
+use std::f64::consts::PI;
+
+use config::Config;
+use world::maps::Maps;
+use world::mesh::Mesh;
+use world::orbit::Spin;
+
+use glium::*;
+use glium::backend::Facade;
+use glium::index::{PrimitiveType, NoIndices};
+use glium::texture::{RawImage2d, Texture2d, MipmapsOption};
+use glium::uniforms::SamplerWrapFunction;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Flat map projections of the whole planet
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Projection {
+    Equirectangular,
+    Mollweide,
+    /// The hemisphere facing the viewer, turning with the planet
+    Orthographic,
+}
+
+impl Projection {
+    /// Returns the next projection, or `None` to return to the globe
+    pub fn next(p : Option<Projection>) -> Option<Projection> {
+        match p {
+            None => Some(Projection::Equirectangular),
+            Some(Projection::Equirectangular) => Some(Projection::Mollweide),
+            Some(Projection::Mollweide) => Some(Projection::Orthographic),
+            Some(Projection::Orthographic) => None,
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+struct Vertex {
+    position: [f32; 2],
+}
+implement_vertex!(Vertex, position);
+
+const VERTEX_SHADER_SRC : &'static str = r#"
+#version 410
+
+in vec2 position;
+out vec2 frag_position;
+
+void main() {
+    gl_Position = vec4(position, 0.0, 1.0);
+    frag_position = position;
+}
+"#;
+
+const FRAGMENT_SHADER_SRC : &'static str = r#"
+#version 410
+
+#define PI 3.1415926535897932384626433832795
+#define SQRT2 1.4142135623730951
+
+uniform sampler2D tex;
+uniform int projection;
+uniform bool graticule;
+uniform float center;
+uniform float aspect;
+
+in vec2 frag_position;
+
+out vec4 color_out;
+
+void main()
+{
+    // Fit the map (2:1, or a disc for the orthographic view) inside the
+    // window without stretching it, leaving a small margin around it
+    vec2 p = frag_position * vec2(aspect, 1.0);
+    p /= 0.95 * (projection == 2 ? min(aspect, 1.0) : min(aspect, 2.0));
+
+    // Invert the projection to find the latitude and longitude under
+    // this pixel
+    float lat;
+    float lon;
+    if (projection == 0) {
+        lon = p.x * PI;
+        lat = p.y * PI;
+        if (abs(lat) > PI / 2.0 || abs(lon) > PI) {
+            discard;
+        }
+    } else if (projection == 1) {
+        vec2 q = p * 2.0 * SQRT2;
+        if (abs(q.y) > SQRT2) {
+            discard;
+        }
+        float theta = asin(q.y / SQRT2);
+        lon = PI * q.x / (2.0 * SQRT2 * cos(theta));
+        if (abs(lon) > PI) {
+            discard;
+        }
+        lat = asin((2.0 * theta + sin(2.0 * theta)) / PI);
+    } else {
+        float r = dot(p, p);
+        if (r > 1.0) {
+            discard;
+        }
+        lat = asin(p.y);
+        lon = center + atan(p.x, sqrt(1.0 - r));
+    }
+
+    vec3 color = texture(tex, vec2((lon + PI) / (2.0 * PI), 0.5 - lat / PI)).rgb;
+
+    // Lines every 30 degrees, a pixel or so wide
+    if (graticule) {
+        vec2 deg = vec2(lon, lat) * 180.0 / PI / 30.0;
+        vec2 d = abs(fract(deg - 0.5) - 0.5) / max(fwidth(deg), vec2(1e-4));
+        float line = 1.0 - clamp(min(d.x, d.y), 0.0, 1.0);
+        color = mix(color, vec3(1.0), 0.5 * line);
+    }
+    color_out = vec4(color, 1.0);
+}
+"#;
+
+/// A flat map of the planet's surface
+pub struct MapView {
+    vbo : VertexBuffer<Vertex>,
+    program : Program,
+    tex : Texture2d,
+    spin : Spin,
+}
+
+impl MapView {
+    /// Builds a map from the terrain already generated for the globe,
+    /// so that it doesn't have to be generated again
+    pub fn new<F>(facade : &F, config : &Config, mesh : Option<&Mesh>)
+        -> Result<MapView, Box<Error>>
+        where F : Facade
+    {
+        // The map samples an equirectangular texture, which is the same
+        // one drawn on a gas giant, or built from the terrain's biomes
+        let img = match (config.gas_giant.as_ref(), mesh) {
+            (Some(g), _) => g.texture(1024, 512)?,
+            (None, Some(mesh)) => Maps::new(config, mesh, 1024).albedo(config.ocean),
+            (None, None) => return Err(From::from("No terrain to map")),
+        };
+        let image_dimensions = img.dimensions();
+        let img = RawImage2d::from_raw_rgb(img.into_raw(), image_dimensions);
+        let tex = Texture2d::with_mipmaps(facade, img, MipmapsOption::NoMipmap)?;
+
+        let quad = [Vertex { position : [-1.0, -1.0] }, Vertex { position : [1.0, -1.0] },
+                    Vertex { position : [-1.0,  1.0] }, Vertex { position : [1.0,  1.0] }];
+        let v = VertexBuffer::new(facade, &quad)?;
+        let p = Program::from_source(facade, VERTEX_SHADER_SRC,
+                                     FRAGMENT_SHADER_SRC, None)?;
+        Ok(MapView { vbo : v, program : p, tex : tex, spin : config.spin.clone() })
+    }
+
+    pub fn draw(&self, projection : Projection, graticule : bool, counter : i32,
+                frame : &mut Frame, params : &DrawParameters) {
+        // The globe turns the body by the spin angle about its pole, which
+        // brings longitude (angle - 90 degrees) round to face the viewer
+        let center = self.spin.turn(counter as f64) - PI / 2.0;
+        let uniforms = uniform! {
+            tex : self.tex.sampled().wrap_function(SamplerWrapFunction::Repeat),
+            projection : match projection {
+                Projection::Equirectangular => 0,
+                Projection::Mollweide => 1,
+                Projection::Orthographic => 2,
+            },
+            graticule : graticule,
+            center : center as f32,
+            aspect : {
+                let (w, h) = frame.get_dimensions();
+                w as f32 / h.max(1) as f32
+            },
+        };
+        frame.draw(&self.vbo, NoIndices(PrimitiveType::TriangleStrip),
+                   &self.program, &uniforms, params).unwrap();
+    }
+}
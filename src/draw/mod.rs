@@ -1,11 +1,21 @@
 pub mod draw;
 pub mod icosphere;
 pub mod util;
+pub mod overlay;
+pub mod debug;
+pub mod layer;
 
 pub mod stars;
+pub mod map;
 
+pub mod scene;
 pub mod planet;
+pub mod registry;
 pub mod terrain;
 pub mod clouds;
 pub mod atmosphere;
 pub mod ocean;
+pub mod rivers;
+pub mod lakes;
+pub mod gas_giant;
+pub mod rings;
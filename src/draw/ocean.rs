@@ -1,6 +1,9 @@
 extern crate cgmath;
-extern crate noise;
 
+use config::Config;
+use draw::debug::{DebugView, wireframe, with_debug};
+use draw::layer::{Context, Layer};
+use draw::overlay::with_overlays;
 use draw::icosphere::icosphere;
 
 use std::error::Error;
@@ -10,9 +13,7 @@ use glium::backend::Facade;
 use glium::index::{PrimitiveType};
 
 use self::cgmath::conv::*;
-use self::cgmath::{Matrix4};
-
-use self::noise::NoiseFn;
+use self::cgmath::{Vector3, InnerSpace};
 
 ////////////////////////////////////////////////////////////////////////////////
 
@@ -31,13 +32,15 @@ uniform mat4 M;
 in vec3 position;
 in float shade;
 
+out vec3 frag_model;
 out vec3 frag_normal;
 out float frag_shade;
 
 void main() {
     gl_Position = M * vec4(position, 1.0);
 
-    frag_normal = normalize(gl_Position.xyz);
+    frag_model = position;
+    frag_normal = normalize((M * vec4(position, 0.0)).xyz);
     frag_shade = shade;
 }
 "#;
@@ -45,6 +48,10 @@ void main() {
 const FRAGMENT_SHADER_SRC : &'static str = r#"
 #version 410
 
+uniform vec3 color;
+uniform vec3 sun;
+
+in vec3 frag_model;
 in vec3 frag_normal;
 in float frag_shade;
 
@@ -52,8 +59,14 @@ out vec4 color_out;
 
 void main()
 {
-    float shade = dot(frag_normal, normalize(vec3(0.5f, 0.5f, -1.0f))) * 0.8;
-    vec3 blue = vec3(0.1, 0.2, 0.5) * shade + vec3(0.01, 0.05, 0.2) * (1 - shade);
+    // The ocean is a sphere, so its normals are its positions
+    if (debug_view == DEBUG_NORMALS) {
+        color_out = vec4(0.5 + 0.5 * normalize(frag_model), 1.0);
+        return;
+    }
+
+    float shade = max(dot(frag_normal, normalize(sun)), 0.0) * 0.8;
+    vec3 blue = color * shade + color * vec3(0.1, 0.25, 0.4) * (1 - shade);
 
     if (shade + frag_shade * 0.03 > 0.79) {
         blue += vec3(0.4, 0.4, 0.2);
@@ -66,7 +79,7 @@ void main()
         blue += frag_shade * 0.02;
     }
 
-    color_out = vec4(blue, 1.0f);
+    color_out = vec4(overlay(blue, frag_model), 1.0f);
 }
 "#;
 
@@ -74,21 +87,33 @@ pub struct Ocean {
     vbo : VertexBuffer<Vertex>,
     indices : IndexBuffer<u32>,
     program : Program,
+    color : [f32; 3],
+    axial_tilt : f64,
+}
+
+/// Returns the ocean's icosphere, with its surface `sea_level` above
+/// the unit sphere
+pub fn sphere(sea_level : f64) -> (Vec<Vector3<f64>>, Vec<Vector3<usize>>) {
+    let (v, i) = icosphere(5);
+    (v.iter().map(|v| { *v * (1.0 + sea_level) }).collect(), i)
 }
 
 impl Ocean {
-    pub fn new<F>(facade : &F) -> Result<Ocean, Box<Error>>
+    /// Builds an ocean sphere with its surface `sea_level` above the
+    /// unit sphere
+    pub fn new<F>(facade : &F, config : &Config, color : [f32; 3], sea_level : f64)
+        -> Result<Ocean, Box<Error>>
         where F : Facade
     {
-        let (v, i) = icosphere(5);
+        let (v, i) = sphere(sea_level);
 
 
-        let per = noise::ScalePoint::new(noise::Perlin::new())
-            .set_all_scales(20.0, 20.0, 20.0, 1.0);
+        let per = config.ocean_noise.compile()?;
 
         let mut buffer : Vec<Vertex> = Vec::new();
         v.iter().for_each(|v| {
-            buffer.push(Vertex { position : [v[0] as f32, v[1] as f32, v[2] as f32], shade : per.get([v[0], v[1], v[2]]) as f32 });
+            let n = v.normalize();
+            buffer.push(Vertex { position : [v[0] as f32, v[1] as f32, v[2] as f32], shade : per.get([n[0], n[1], n[2]]) as f32 });
         });
 
         let mut indices : Vec<u32> = Vec::new();
@@ -100,26 +125,40 @@ impl Ocean {
 
         let v = VertexBuffer::new(facade, &buffer)?;
         let p = Program::from_source(facade, VERTEX_SHADER_SRC,
-                                     FRAGMENT_SHADER_SRC, None)?;
+                                     &with_debug(&with_overlays(FRAGMENT_SHADER_SRC)), None)?;
 
         let i = IndexBuffer::new(facade, PrimitiveType::TrianglesList,
                                  &indices)?;
-        Ok(Ocean { vbo : v, indices : i, program : p })
+        Ok(Ocean { vbo : v, indices : i, program : p, color : color,
+                   axial_tilt : config.spin.axial_tilt })
     }
+}
 
-    pub fn draw(&self, mat : Matrix4<f32>, frame : &mut Frame, params : &DrawParameters) {
+impl Layer for Ocean {
+    /// Draws the ocean, with graticule and tropics overlays (contours
+    /// are drawn on the seafloor, so they only show where it's dry)
+    fn draw(&self, ctx : &Context, frame : &mut Frame) {
+        if DebugView::surface_only(ctx.debug) {
+            return;
+        }
 
-        let params = DrawParameters {
+        let params = wireframe(ctx.debug, DrawParameters {
             depth : Depth {
                 test: DepthTest::IfLess,
                 write: true,
                 .. Default::default()
             },
-            .. params.clone()
-        };
+            .. ctx.params.clone()
+        });
 
         let uniforms = uniform! {
-            M : array4x4(mat),
+            M : array4x4(ctx.mat),
+            sun : ctx.sun,
+            color : self.color,
+            show_graticule : ctx.overlays.graticule,
+            show_tropics : ctx.overlays.tropics,
+            axial_tilt : self.axial_tilt as f32,
+            debug_view : DebugView::uniform(ctx.debug),
         };
 
         frame.draw(&self.vbo, &self.indices, &self.program,
@@ -0,0 +1,63 @@
+use draw::util::after_version;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Reference lines drawn over the planet's surface
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Overlays {
+    /// Latitude and longitude lines every 15 degrees
+    pub graticule : bool,
+    /// Elevation contours, at the configured interval
+    pub contours : bool,
+    /// The equator and both tropics
+    pub tropics : bool,
+}
+
+/// Functions shared by every fragment shader that draws overlays.
+///
+/// Positions are in the body's own frame, so lines turn with the planet.
+const OVERLAY_SRC : &'static str = r#"
+#define PI 3.1415926535897932384626433832795
+
+uniform bool show_graticule;
+uniform bool show_tropics;
+uniform float axial_tilt;
+
+// Coverage (0 to 1) of lines spaced evenly through v, about a pixel wide
+float lines(float v, float spacing)
+{
+    float x = v / spacing;
+    float d = abs(fract(x - 0.5) - 0.5) / max(fwidth(x), 1e-4);
+    return 1.0 - clamp(d, 0.0, 1.0);
+}
+
+// Coverage of a single line where v equals target
+float line_at(float v, float target)
+{
+    float d = abs(v - target) / max(fwidth(v), 1e-4);
+    return 1.0 - clamp(d, 0.0, 1.0);
+}
+
+vec3 overlay(vec3 color, vec3 model_position)
+{
+    vec3 n = normalize(model_position);
+    float lat = degrees(asin(n.y));
+    if (show_graticule) {
+        // The jump at +/-180 falls on a line anyway, so it doesn't show
+        float lon = degrees(atan(n.z, n.x));
+        float g = max(lines(lat, 15.0), lines(lon, 15.0));
+        color = mix(color, vec3(1.0), 0.4 * g);
+    }
+    if (show_tropics) {
+        color = mix(color, vec3(1.0, 0.3, 0.2), 0.8 * line_at(lat, 0.0));
+        float t = max(line_at(lat, axial_tilt), line_at(lat, -axial_tilt));
+        color = mix(color, vec3(1.0, 0.8, 0.2), 0.8 * t);
+    }
+    return color;
+}
+"#;
+
+/// Adds the overlay functions to a fragment shader
+pub fn with_overlays(src : &str) -> String {
+    after_version(src, OVERLAY_SRC)
+}
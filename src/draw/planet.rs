@@ -3,46 +3,154 @@ extern crate glium;
 
 use std::error::Error;
 
+use config::Config;
+
 use draw::ocean::Ocean;
 use draw::terrain::Terrain;
 use draw::atmosphere::Atmosphere;
 use draw::clouds::Clouds;
+use draw::rivers::Rivers;
+use draw::lakes::Lakes;
+use draw::gas_giant::GasGiantLayer;
+use draw::layer::{Context, Layer};
+use draw::registry::Registry;
+use draw::rings::RingLayer;
+use world::decimate::decimate;
+use world::mesh::Mesh;
+use world::orbit::Spin;
 
 use self::glium::*;
-use self::cgmath::{Vector4, Matrix4, Rad, SquareMatrix, Transform, Array};
+use self::cgmath::{Matrix3, Matrix4, Transform};
 
+/// A stack of layers, drawn in order
 pub struct Planet
 {
-    terrain: Option<Terrain>,
-    ocean : Option<Ocean>,
-    clouds : Option<Clouds>,
-    atmosphere : Option<Atmosphere>,
+    /// Layers by name, which can be reordered, shown, or hidden at runtime
+    pub layers : Registry<Box<Layer>>,
+    spin : Spin,
+    /// Full-detail terrain, kept for the flat map; `None` for gas giants
+    mesh : Option<Mesh>,
+}
+
+/// Every layer a planet can have, in their usual draw order.
+///
+/// The rings are translucent and don't write depth, so their far half
+/// goes under the clouds and their near half over everything.
+const LAYERS : [&'static str; 9] = ["GasGiant", "Terrain", "Lakes", "Rivers", "Ocean",
+                                    "RingsBack", "Clouds", "Atmosphere", "RingsFront"];
+
+fn boxed<L>(layer : Result<L, Box<Error>>) -> Option<Result<Box<Layer>, Box<Error>>>
+    where L : Layer + 'static
+{
+    Some(layer.map(|l| { Box::new(l) as Box<Layer> }))
+}
+
+/// Builds one of the named layers, or returns `None` if the config
+/// doesn't call for it
+fn build(display : &glium::Display, config : &Config, mesh : Option<&Mesh>,
+         low_poly : Option<&Mesh>, name : &str) -> Option<Result<Box<Layer>, Box<Error>>>
+{
+    // Gas giants replace the whole terrain / ocean / clouds stack
+    match (name, config.gas_giant.as_ref(), mesh) {
+        ("GasGiant", Some(g), _) => boxed(GasGiantLayer::new(display, g)),
+        ("Terrain", None, Some(m)) =>
+            boxed(Terrain::new(display, config, low_poly.unwrap_or(m))),
+        // Lakes and rivers follow the full mesh, so they would float
+        // above or sink into simplified terrain; leave them out
+        ("Lakes", None, Some(m)) => match (m.hydrology.as_ref(), low_poly) {
+            (Some(h), None) => boxed(Lakes::new(display, m, h)),
+            _ => None,
+        },
+        ("Rivers", None, Some(m)) => match (m.hydrology.as_ref(), low_poly) {
+            (Some(h), None) => boxed(Rivers::new(display, m, h)),
+            _ => None,
+        },
+        ("Ocean", None, Some(m)) => config.ocean.and_then(|color| {
+            boxed(Ocean::new(display, config, color, m.sea_level)) }),
+        ("RingsBack", _, _) => config.rings.as_ref().and_then(|r| {
+            boxed(RingLayer::new(display, r, false)) }),
+        ("Clouds", None, _) if config.cloud_coverage > 0.0 =>
+            boxed(Clouds::new(display, config)),
+        ("Atmosphere", _, _) => config.atmosphere.and_then(|color| {
+            boxed(Atmosphere::new(display, color)) }),
+        ("RingsFront", _, _) => config.rings.as_ref().and_then(|r| {
+            boxed(RingLayer::new(display, r, true)) }),
+        _ => None,
+    }
 }
 
 impl Planet {
-    pub fn new(display : &glium::Display) -> Result<Planet, Box<Error>> {
-        Ok(Planet {
-            terrain : Terrain::new(display).map_err(|err| {
-                println!("Couldn't construct Terrain: {}", err); }).ok(),
-            ocean : Ocean::new(display).map_err(|err| {
-                println!("Couldn't construct Ocean: {}", err); }).ok(),
-            atmosphere : Atmosphere::new(display).map_err(|err| {
-                println!("Couldn't construct Atmosphere: {}", err); }).ok(),
-            clouds : Clouds::new(display).map_err(|err| {
-                println!("Couldn't construct Clouds: {}", err); }).ok(),
-        })
+    /// Builds a planet with every layer that the config calls for.
+    /// Layers that fail to build are reported and left out.
+    pub fn new(display : &glium::Display, config : &Config) -> Planet {
+        let mut planet = Planet { layers : Registry::new(), spin : config.spin.clone(),
+                                  mesh : None };
+        planet.reload(display, config);
+        planet
     }
 
-    pub fn draw(&self, counter: i32, frame: &mut Frame, params: &glium::DrawParameters) {
+    /// Rebuilds every layer from a new config, swapping each one in
+    /// where the old one was drawn and keeping it shown or hidden.
+    /// Layers that the config no longer calls for are removed, and new
+    /// ones are added next to their usual neighbors.
+    pub fn reload(&mut self, display : &glium::Display, config : &Config) {
+        self.spin = config.spin.clone();
+        self.mesh = match config.gas_giant {
+            Some(_) => None,
+            None => Some(Mesh::generate(config)),
+        };
+        let low_poly = match (self.mesh.as_ref(), config.low_poly) {
+            (Some(m), Some(n)) => Some(decimate(m, n)),
+            _ => None,
+        };
 
-        let mut scale = Vector4::from_value(0.7f32);
-        scale.w = 1f32;
-        let mut mat = Matrix4::from_diagonal(scale);
-        mat.concat_self(&Matrix4::from_angle_y(Rad(counter as f32 / 100f32)));
+        // Where the next new layer goes
+        let mut next = 0;
+        for name in LAYERS.iter() {
+            match build(display, config, self.mesh.as_ref(), low_poly.as_ref(), name) {
+                Some(Ok(layer)) => match self.layers.position(name) {
+                    Some(i) => {
+                        self.layers.replace(name, layer);
+                        next = i + 1;
+                    },
+                    None => {
+                        self.layers.insert(next, name, layer);
+                        next += 1;
+                    },
+                },
+                Some(Err(err)) => println!("Couldn't construct {}: {}", name, err),
+                None => if let Some(i) = self.layers.position(name) {
+                    self.layers.remove(name);
+                    if i < next {
+                        next -= 1;
+                    }
+                },
+            }
+        }
+    }
+
+    /// The terrain that this planet was built from, if it has any
+    pub fn mesh(&self) -> Option<&Mesh> {
+        self.mesh.as_ref()
+    }
 
-        self.terrain.as_ref().map(|t| { t.draw(mat, frame, &params); });
-        self.ocean.as_ref().map(|o| { o.draw(mat, frame, &params); });
-        self.clouds.as_ref().map(|c| { c.draw(mat, frame, &params); });
-        self.atmosphere.as_ref().map(|a| { a.draw(frame, &params) });
+    /// Draws the planet spinning about its tilted axis, with the
+    /// context's `model` placing and scaling it in the scene
+    pub fn draw(&self, ctx : &Context, frame : &mut Frame) {
+        let turn = |r : Matrix3<f64>| {
+            let mut mat = ctx.model;
+            mat.concat_self(&Matrix4::new(
+                r.x.x as f32, r.x.y as f32, r.x.z as f32, 0.0,
+                r.y.x as f32, r.y.y as f32, r.y.z as f32, 0.0,
+                r.z.x as f32, r.z.y as f32, r.z.z as f32, 0.0,
+                0.0, 0.0, 0.0, 1.0));
+            mat
+        };
+        let time = ctx.counter as f64;
+        let ctx = Context { mat : turn(self.spin.orientation(time)),
+                            axis : turn(self.spin.axis(time)), .. *ctx };
+        for layer in self.layers.enabled() {
+            layer.draw(&ctx, frame);
+        }
     }
 }
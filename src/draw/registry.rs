@@ -0,0 +1,140 @@
+/// A named entry in a draw order
+struct Slot<T> {
+    name : String,
+    item : T,
+    enabled : bool,
+}
+
+/// Named items in a draw order, each of which can be shown or hidden.
+///
+/// This only does the bookkeeping, so it doesn't need a display to hold
+/// (or test) anything; `Planet` uses it for its layers.
+pub struct Registry<T> {
+    slots : Vec<Slot<T>>,
+}
+
+impl<T> Registry<T> {
+    pub fn new() -> Registry<T> {
+        Registry { slots : Vec::new() }
+    }
+
+    /// Returns the position of an item in the draw order
+    pub fn position(&self, name : &str) -> Option<usize> {
+        self.slots.iter().position(|s| { s.name == name })
+    }
+
+    /// Returns each item's name and whether it's drawn, in draw order
+    pub fn names(&self) -> Vec<(&str, bool)> {
+        self.slots.iter().map(|s| { (&s.name[..], s.enabled) }).collect()
+    }
+
+    /// Returns the items that are drawn, in draw order
+    pub fn enabled<'a>(&'a self) -> Box<Iterator<Item=&'a T> + 'a> {
+        Box::new(self.slots.iter().filter(|s| { s.enabled }).map(|s| { &s.item }))
+    }
+
+    /// Adds an item at a position in the draw order
+    pub fn insert(&mut self, index : usize, name : &str, item : T) {
+        let index = index.min(self.slots.len());
+        self.slots.insert(index, Slot { name : name.to_string(), item : item,
+                                        enabled : true });
+    }
+
+    /// Removes an item, returning it if it was present
+    pub fn remove(&mut self, name : &str) -> Option<T> {
+        self.position(name).map(|i| { self.slots.remove(i).item })
+    }
+
+    /// Swaps in a new item, keeping the old one's place and visibility,
+    /// and returns the old one.  If there was no item with this name,
+    /// the new one is added on top.
+    pub fn replace(&mut self, name : &str, item : T) -> Option<T> {
+        match self.position(name) {
+            Some(i) => Some(::std::mem::replace(&mut self.slots[i].item, item)),
+            None => {
+                let index = self.slots.len();
+                self.insert(index, name, item);
+                None
+            },
+        }
+    }
+
+    /// Moves an item to a new position in the draw order, returning
+    /// false if there's no item with this name
+    pub fn move_to(&mut self, name : &str, index : usize) -> bool {
+        match self.position(name) {
+            Some(i) => {
+                let slot = self.slots.remove(i);
+                let index = index.min(self.slots.len());
+                self.slots.insert(index, slot);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Shows or hides an item, returning false if there's no item
+    /// with this name
+    pub fn set_enabled(&mut self, name : &str, enabled : bool) -> bool {
+        match self.position(name) {
+            Some(i) => {
+                self.slots[i].enabled = enabled;
+                true
+            },
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn abc() -> Registry<u32> {
+        let mut r = Registry::new();
+        r.insert(0, "a", 1);
+        r.insert(1, "b", 2);
+        r.insert(99, "c", 3);
+        r
+    }
+
+    fn order(r : &Registry<u32>) -> Vec<&str> {
+        r.names().into_iter().map(|(n, _)| { n }).collect()
+    }
+
+    #[test]
+    fn insert_clamps_and_remove_returns() {
+        let mut r = abc();
+        assert_eq!(order(&r), vec!["a", "b", "c"]);
+        r.insert(0, "d", 4);
+        assert_eq!(order(&r), vec!["d", "a", "b", "c"]);
+
+        assert_eq!(r.remove("b"), Some(2));
+        assert_eq!(r.remove("b"), None);
+        assert_eq!(order(&r), vec!["d", "a", "c"]);
+    }
+
+    #[test]
+    fn replace_keeps_place_and_visibility() {
+        let mut r = abc();
+        r.set_enabled("b", false);
+        assert_eq!(r.replace("b", 20), Some(2));
+        assert_eq!(r.names(), vec![("a", true), ("b", false), ("c", true)]);
+        assert_eq!(r.enabled().cloned().collect::<Vec<_>>(), vec![1, 3]);
+
+        assert_eq!(r.replace("d", 4), None);
+        assert_eq!(order(&r), vec!["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn move_to_reorders() {
+        let mut r = abc();
+        assert!(r.move_to("c", 0));
+        assert_eq!(order(&r), vec!["c", "a", "b"]);
+        assert!(r.move_to("c", 99));
+        assert_eq!(order(&r), vec!["a", "b", "c"]);
+        assert!(!r.move_to("d", 0));
+        assert!(!r.set_enabled("d", false));
+        assert_eq!(r.enabled().cloned().collect::<Vec<_>>(), vec![1, 2, 3]);
+    }
+}
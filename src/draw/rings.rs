@@ -0,0 +1,163 @@
+extern crate cgmath;
+
+use std::error::Error;
+use std::f32::consts::PI;
+
+use draw::layer::{Context, Layer};
+use world::rings::Rings;
+
+use glium::*;
+use glium::backend::Facade;
+use glium::index::{PrimitiveType, NoIndices};
+use glium::texture::{RawImage2d, Texture2d};
+use glium::uniforms::SamplerWrapFunction;
+
+use self::cgmath::{Matrix4, Deg};
+use self::cgmath::conv::*;
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Copy, Clone)]
+struct Vertex {
+    position : [f32; 2],
+    radius : f32,
+}
+implement_vertex!(Vertex, position, radius);
+
+const VERTEX_SHADER_SRC : &'static str = r#"
+#version 410
+
+uniform mat4 M;
+
+in vec2 position;
+in float radius;
+
+out vec3 frag_position;
+out float frag_radius;
+
+void main() {
+    // Rings lie in the planet's equatorial (XZ) plane
+    gl_Position = M * vec4(position.x, 0.0, position.y, 1.0);
+    frag_position = gl_Position.xyz - (M * vec4(0.0, 0.0, 0.0, 1.0)).xyz;
+    frag_radius = radius;
+}
+"#;
+
+const FRAGMENT_SHADER_SRC : &'static str = r#"
+#version 410
+
+uniform sampler2D tex;
+uniform vec3 color;
+uniform vec3 light;
+uniform float planet_radius;
+uniform bool front;
+
+// Position relative to the planet's center
+in vec3 frag_position;
+in float frag_radius;
+
+out vec4 color_out;
+
+void main()
+{
+    // Each half is drawn separately, so that clouds can fall between them.
+    // The viewer looks down +Z, so the near half has negative Z.
+    if (front != (frag_position.z < 0.0)) {
+        discard;
+    }
+    float density = texture(tex, vec2(frag_radius, 0.5)).r;
+
+    // Check whether the ray towards the light passes through the planet
+    vec3 L = normalize(light);
+    float t = -dot(frag_position, L);
+    float shade = 1.0;
+    if (t > 0.0 && length(frag_position + t * L) < planet_radius) {
+        shade = 0.15;
+    }
+
+    color_out = vec4(color * shade, density);
+}
+"#;
+
+/// Either the near or the far half of a ring system
+pub struct RingLayer {
+    vbo : VertexBuffer<Vertex>,
+    program : Program,
+    tex : Texture2d,
+    params : Rings,
+    front : bool,
+}
+
+impl RingLayer {
+    /// Builds the half of the rings nearer to the viewer if `front` is
+    /// set, or the half behind the planet's center otherwise
+    pub fn new<F>(facade : &F, params : &Rings, front : bool) -> Result<RingLayer, Box<Error>>
+        where F : Facade
+    {
+        let n = 256;
+        let (inner, outer) = (params.inner as f32, params.outer as f32);
+        let mut buffer : Vec<Vertex> = Vec::new();
+        for i in 0..n {
+            let a = (i as f32) / (n as f32) * 2f32 * PI;
+            let b = ((i + 1) as f32) / (n as f32) * 2f32 * PI;
+            let v = |angle : f32, r : f32, f : f32| {
+                Vertex { position : [angle.cos() * r, angle.sin() * r], radius : f }
+            };
+            buffer.push(v(a, inner, 0.0));
+            buffer.push(v(a, outer, 1.0));
+            buffer.push(v(b, outer, 1.0));
+
+            buffer.push(v(a, inner, 0.0));
+            buffer.push(v(b, outer, 1.0));
+            buffer.push(v(b, inner, 0.0));
+        }
+
+        let profile = params.profile(512)?;
+        let raw : Vec<u8> = profile.iter().flat_map(|d| {
+            let d = (d * 255.0) as u8;
+            vec![d, d, d]
+        }).collect();
+        let img = RawImage2d::from_raw_rgb(raw, (profile.len() as u32, 1));
+        let tex = Texture2d::new(facade, img)?;
+
+        let v = VertexBuffer::new(facade, &buffer)?;
+        let p = Program::from_source(facade, VERTEX_SHADER_SRC,
+                                     FRAGMENT_SHADER_SRC, None)?;
+        Ok(RingLayer { vbo : v, program : p, tex : tex, params : params.clone(),
+                       front : front })
+    }
+}
+
+impl Layer for RingLayer {
+    /// Draws the rings inclined to the planet's tilted equator.  They
+    /// don't turn with the planet's day, and since the shadow test works
+    /// on the transformed positions, it follows the ring plane's tilt.
+    fn draw(&self, ctx : &Context, frame : &mut Frame) {
+        let mat = ctx.axis * Matrix4::from_angle_x(Deg(self.params.tilt as f32));
+
+        // Test against the planet's depth (so that it hides the far side
+        // of the rings), but don't write, since the rings are translucent.
+        let params = DrawParameters {
+            depth : Depth {
+                test: DepthTest::IfLess,
+                write: false,
+                .. Default::default()
+            },
+            blend : draw_parameters::Blend::alpha_blending(),
+            .. ctx.params.clone()
+        };
+
+        let indices = NoIndices(PrimitiveType::TrianglesList);
+        let uniforms = uniform! {
+            M : array4x4(mat),
+            tex : self.tex.sampled().wrap_function(SamplerWrapFunction::Clamp),
+            color : self.params.color,
+            light : ctx.sun,
+            planet_radius : mat.x.x.hypot(mat.x.y).hypot(mat.x.z),
+            front : self.front,
+        };
+
+        frame.draw(&self.vbo, indices, &self.program,
+                   &uniforms, &params).unwrap();
+    }
+}
@@ -0,0 +1,115 @@
+extern crate cgmath;
+
+use std::error::Error;
+
+use draw::debug::DebugView;
+use draw::layer::{Context, Layer};
+use world::hydrology::Hydrology;
+use world::mesh::Mesh;
+
+use glium::*;
+use glium::backend::Facade;
+use glium::index::{PrimitiveType, NoIndices};
+
+use self::cgmath::conv::*;
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Copy, Clone)]
+struct Vertex {
+    position : [f32; 3],
+    flow : f32,
+}
+implement_vertex!(Vertex, position, flow);
+
+const VERTEX_SHADER_SRC : &'static str = r#"
+#version 410
+
+uniform mat4 M;
+
+in vec3 position;
+in float flow;
+
+out float frag_flow;
+
+void main() {
+    gl_Position = M * vec4(position, 1.0);
+    frag_flow = flow;
+}
+"#;
+
+const FRAGMENT_SHADER_SRC : &'static str = r#"
+#version 410
+
+in float frag_flow;
+
+out vec4 color_out;
+
+void main()
+{
+    // Small streams fade into the terrain, big rivers are solid
+    color_out = vec4(0.15, 0.3, 0.6, clamp(0.4 + frag_flow, 0.0, 1.0));
+}
+"#;
+
+pub struct Rivers {
+    vbo : VertexBuffer<Vertex>,
+    program : Program,
+}
+
+impl Rivers {
+    pub fn new<F>(facade : &F, mesh : &Mesh, hydrology : &Hydrology)
+        -> Result<Rivers, Box<Error>>
+        where F : Facade
+    {
+        // Flow is stored as a fraction of the planet's rainfall, so use
+        // a log scale relative to the smallest river.
+        let min = hydrology.rivers.iter()
+            .map(|&(i, _)| { hydrology.flow[i] })
+            .fold(1.0, f64::min);
+
+        let mut buffer : Vec<Vertex> = Vec::new();
+        for &(a, b) in hydrology.rivers.iter() {
+            let flow = ((hydrology.flow[a] / min).ln() / 5.0) as f32;
+            for &i in [a, b].iter() {
+                // Lift the river slightly so it doesn't z-fight the terrain
+                let p = mesh.verts[i] * 1.001;
+                buffer.push(Vertex { position : [p.x as f32, p.y as f32, p.z as f32],
+                                     flow : flow });
+            }
+        }
+
+        let v = VertexBuffer::new(facade, &buffer)?;
+        let p = Program::from_source(facade, VERTEX_SHADER_SRC,
+                                     FRAGMENT_SHADER_SRC, None)?;
+        Ok(Rivers { vbo : v, program : p })
+    }
+}
+
+impl Layer for Rivers {
+    /// Draws the rivers, which are already lines in the wireframe view
+    /// and are hidden in the other debug views, like the lakes
+    fn draw(&self, ctx : &Context, frame : &mut Frame) {
+        if ctx.debug.is_some() && ctx.debug != Some(DebugView::Wireframe) {
+            return;
+        }
+
+        let params = DrawParameters {
+            depth : Depth {
+                test: DepthTest::IfLess,
+                write: false,
+                .. Default::default()
+            },
+            blend : draw_parameters::Blend::alpha_blending(),
+            .. ctx.params.clone()
+        };
+
+        let indices = NoIndices(PrimitiveType::LinesList);
+        let uniforms = uniform! {
+            M : array4x4(ctx.mat),
+        };
+
+        frame.draw(&self.vbo, indices, &self.program,
+                   &uniforms, &params).unwrap();
+    }
+}
@@ -0,0 +1,123 @@
+extern crate cgmath;
+extern crate glium;
+
+use config::Config;
+use draw::layer::Context;
+use draw::planet::Planet;
+use world::mesh::Mesh;
+use world::orbit::Orbit;
+
+use self::glium::*;
+use self::cgmath::{Matrix4, Vector3, Transform};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// A planet and the bodies orbiting it
+struct Body {
+    planet : Planet,
+    /// Orbit around the parent body; `None` for the root
+    orbit : Option<Orbit>,
+    /// Radius relative to the parent body
+    size : f32,
+    children : Vec<Body>,
+}
+
+impl Body {
+    fn new(display : &glium::Display, config : &Config,
+           orbit : Option<Orbit>, size : f32) -> Body {
+        Body {
+            planet : Planet::new(display, config),
+            orbit : orbit,
+            size : size,
+            children : config.moons.iter().map(|m| {
+                Body::new(display, &m.config, Some(m.orbit.clone()), m.size as f32)
+            }).collect(),
+        }
+    }
+
+    /// Rebuilds this body's layers in place from a new config.  Moons
+    /// are reloaded one-for-one if their count is unchanged, and rebuilt
+    /// from scratch otherwise.
+    fn reload(&mut self, display : &glium::Display, config : &Config) {
+        self.planet.reload(display, config);
+        if self.children.len() == config.moons.len() {
+            for (c, m) in self.children.iter_mut().zip(config.moons.iter()) {
+                c.orbit = Some(m.orbit.clone());
+                c.size = m.size as f32;
+                c.reload(display, &m.config);
+            }
+        } else {
+            self.children = config.moons.iter().map(|m| {
+                Body::new(display, &m.config, Some(m.orbit.clone()), m.size as f32)
+            }).collect();
+        }
+    }
+
+    /// Collects this body's planet and those of its children, depth-first
+    fn planets_mut<'a>(&'a mut self, out : &mut Vec<&'a mut Planet>) {
+        out.push(&mut self.planet);
+        for c in self.children.iter_mut() {
+            c.planets_mut(out);
+        }
+    }
+
+    /// Draws this body and its children, where the context's `model`
+    /// maps the parent's unit sphere into clip space
+    fn draw(&self, ctx : &Context, frame : &mut Frame) {
+        let mut model = ctx.model;
+        if let Some(ref orbit) = self.orbit {
+            let p = orbit.position(ctx.counter as f64);
+            model.concat_self(&Matrix4::from_translation(
+                Vector3::new(p.x as f32, p.y as f32, p.z as f32)));
+        }
+        model.concat_self(&Matrix4::from_scale(self.size));
+
+        let ctx = Context { model : model, mat : model, axis : model, .. *ctx };
+        self.planet.draw(&ctx, frame);
+        for c in self.children.iter() {
+            c.draw(&ctx, frame);
+        }
+    }
+}
+
+/// A tree of bodies, with the configured planet at the root
+pub struct Scene {
+    root : Body,
+    /// Scale that fits the whole system on screen
+    scale : f32,
+}
+
+impl Scene {
+    pub fn new(display : &glium::Display, config : &Config) -> Scene {
+        // A lone planet keeps its usual size; larger systems shrink to fit
+        let scale = (0.9 / config.extent()).min(0.7) as f32;
+        Scene { root : Body::new(display, config, None, 1.0), scale : scale }
+    }
+
+    /// Rebuilds the scene from a new config, keeping each layer's place
+    /// in the draw order and whether it's shown
+    pub fn reload(&mut self, display : &glium::Display, config : &Config) {
+        self.scale = (0.9 / config.extent()).min(0.7) as f32;
+        self.root.reload(display, config);
+    }
+
+    /// Every planet in the scene, root first and then its moons
+    /// depth-first, whose layers can be changed at runtime
+    pub fn planets_mut(&mut self) -> Vec<&mut Planet> {
+        let mut out = Vec::new();
+        self.root.planets_mut(&mut out);
+        out
+    }
+
+    /// The root planet's terrain, if it has any
+    pub fn mesh(&self) -> Option<&Mesh> {
+        self.root.planet.mesh()
+    }
+
+    pub fn draw(&self, ctx : &Context, frame : &mut Frame) {
+        let mut model = ctx.model;
+        model.concat_self(&Matrix4::from_scale(self.scale));
+        self.root.draw(&Context { model : model, mat : model, axis : model, .. *ctx },
+                       frame);
+    }
+}
@@ -1,17 +1,17 @@
 extern crate glium;
 extern crate image;
-extern crate noise;
 
 use std::error::Error;
 
+use config::Config;
+use draw::layer::{Context, Layer};
+
 use glium::*;
 use glium::backend::Facade;
-use glium::uniforms::EmptyUniforms;
 use glium::index::{PrimitiveType, NoIndices};
 use glium::texture::{RawImage2d, Texture2d};
 
-use self::image::{GenericImage, ImageBuffer, ConvertBuffer, RgbImage};
-use self::noise::NoiseFn;
+use self::image::{ImageBuffer, ConvertBuffer, RgbImage};
 
 #[derive(Copy, Clone)]
 struct Vertex {
@@ -36,6 +36,7 @@ const FRAGMENT_SHADER_SRC : &'static str = r#"
 
 in vec2 frag_position;
 uniform sampler2D tex;
+uniform float cutoff;
 
 out vec4 color_out;
 
@@ -43,7 +44,6 @@ void main()
 {
     float r = texture(tex, frag_position).r;
 
-    float cutoff = 0.67;
     if (r < cutoff) {
         r = 0;
     } else {
@@ -58,14 +58,14 @@ pub struct Stars {
     vbo : VertexBuffer<Vertex>,
     program : Program,
     tex: Texture2d,
+    cutoff : f32,
 }
 
 impl Stars {
-    pub fn new<F>(facade : &F) -> Result<Stars, Box<Error>>
+    pub fn new<F>(facade : &F, config : &Config) -> Result<Stars, Box<Error>>
         where F : Facade
     {
-        let per = noise::ScalePoint::new(noise::Perlin::new())
-            .set_all_scales(0.2, 0.2, 0.2, 1.0);
+        let per = config.star_noise.compile()?;
         let img = ImageBuffer::from_fn(512, 512, |x, y| {
             let a = per.get([x as f64, y as f64, 0.0]);
             let a = ((a/2.0 + 0.5) * 255.0) as u8;
@@ -88,15 +88,19 @@ impl Stars {
         let p = Program::from_source(facade, VERTEX_SHADER_SRC,
                                      FRAGMENT_SHADER_SRC, None)?;
 
-        Ok(Stars{ vbo: vbo, program: p, tex: tex })
+        Ok(Stars{ vbo: vbo, program: p, tex: tex, cutoff: config.star_cutoff })
     }
+}
 
-    pub fn draw(&self, frame : &mut Frame, params: &glium::DrawParameters) {
+impl Layer for Stars {
+    /// Draws the star field behind everything, ignoring the transform
+    fn draw(&self, ctx : &Context, frame : &mut Frame) {
         let uniforms = uniform! {
             tex: &self.tex,
+            cutoff: self.cutoff,
         };
 
         let indices = NoIndices(PrimitiveType::TriangleFan);
-        frame.draw(&self.vbo, &indices, &self.program, &uniforms, params);
+        frame.draw(&self.vbo, &indices, &self.program, &uniforms, ctx.params).unwrap();
     }
 }
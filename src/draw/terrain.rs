@@ -1,22 +1,21 @@
 extern crate cgmath;
-extern crate noise;
-extern crate rand;
 
 use std::error::Error;
 
-use draw::icosphere::icosphere;
-use draw::util::Interpolator;
+use config::{Config, Shading};
+use draw::debug::{DebugView, wireframe, with_debug};
+use draw::layer::{Context, Layer};
+use draw::overlay::with_overlays;
+use world::orbit::Spin;
+use world::mesh::Mesh;
 
 use glium::*;
 use glium::backend::Facade;
-use glium::index::{PrimitiveType, NoIndices};
+use glium::draw_parameters::ProvokingVertex;
+use glium::index::PrimitiveType;
 
 use self::cgmath::conv::*;
-use self::cgmath::{Matrix4, Vector3, InnerSpace};
-use self::rand::distributions::{Range, Sample};
-use self::rand::{SeedableRng, ChaChaRng};
-
-use self::noise::NoiseFn;
+use self::cgmath::Vector3;
 
 ////////////////////////////////////////////////////////////////////////////////
 
@@ -25,146 +24,205 @@ struct Vertex {
     position : [f32; 3],
     normal   : [f32; 3],
     color    : [f32; 3],
+    temperature : f32,
+    /// Height above sea level
+    height : f32,
+    moisture : f32,
+    biome : f32,
 }
-implement_vertex!(Vertex, position, normal, color);
+implement_vertex!(Vertex, position, normal, color, temperature, height, moisture, biome);
 
 const VERTEX_SHADER_SRC : &'static str = r#"
 #version 410
 
 uniform mat4 M;
 
+uniform float declination;
+uniform float snow_line;
+uniform float seasonal_swing;
+
 in vec3 position;
 in vec3 normal;
 in vec3 color;
+in float temperature;
+in float height;
+in float moisture;
+in float biome;
 
 out vec3 frag_position;
+out vec3 frag_model;
+out float frag_height;
+out float frag_moisture;
+out float frag_temperature;
+flat out float frag_biome;
 out vec3 frag_normal;
 out vec3 frag_color;
+flat out vec3 frag_color_flat;
 
 void main() {
     gl_Position = M * vec4(position, 1.0);
 
-    frag_position = position;
+    // Whichever hemisphere leans towards the sun warms up, moving the
+    // snow line towards the opposite pole
+    float lat = asin(normalize(position).y);
+    float t = temperature + seasonal_swing * sin(lat) * sin(declination);
+    float snow = smoothstep(snow_line, snow_line - 0.05, t);
+    vec3 c = mix(color, vec3(0.9, 0.92, 0.95), snow);
+
+    frag_position = gl_Position.xyz;
+    frag_model = position;
+    frag_height = height;
+    frag_moisture = moisture;
+    frag_temperature = temperature;
+    frag_biome = biome;
     frag_normal   = (M * vec4(normal, 0.0)).xyz;
-    frag_color = color;
+    frag_color = c;
+    frag_color_flat = c;
 }
 "#;
 
 const FRAGMENT_SHADER_SRC : &'static str = r#"
 #version 410
 
+uniform bool flat_shading;
+uniform vec3 sun;
+uniform bool show_contours;
+uniform float contour_interval;
+uniform float height_range;
+
 in vec3 frag_position;
+in vec3 frag_model;
+in float frag_height;
+in float frag_moisture;
+in float frag_temperature;
+flat in float frag_biome;
 in vec3 frag_normal;
 in vec3 frag_color;
+flat in vec3 frag_color_flat;
 
 out vec4 color_out;
 
+// Normals point into the planet, so they're flipped before lighting
+float light(vec3 norm)
+{
+    return 0.1 + 0.9 * max(dot(-norm, normalize(sun)), 0.0);
+}
+
 void main()
 {
-    color_out = vec4(frag_normal.z * frag_color, 1.0f);
+    vec3 color;
+    if (debug_view == DEBUG_NORMALS) {
+        // The face normal as below, in the body's frame and flipped outwards
+        vec3 norm = normalize(cross(dFdx(frag_model), dFdy(frag_model)));
+        color = 0.5 - 0.5 * norm;
+    } else if (debug_view == DEBUG_ELEVATION) {
+        color = false_color(frag_height / height_range);
+    } else if (debug_view == DEBUG_BIOME) {
+        color = id_color(frag_biome);
+    } else if (debug_view == DEBUG_MOISTURE) {
+        color = mix(vec3(0.6, 0.4, 0.2), vec3(0.1, 0.3, 0.9), clamp(frag_moisture, 0.0, 1.0));
+    } else if (debug_view == DEBUG_TEMPERATURE) {
+        color = mix(vec3(0.2, 0.3, 1.0), vec3(1.0, 0.2, 0.1), clamp(frag_temperature, 0.0, 1.0));
+    } else if (flat_shading && debug_view != DEBUG_WIREFRAME) {
+        // Recover the face normal from screen-space derivatives, since
+        // vertices are shared between triangles in the index buffer
+        // (lines have no area, so wireframes use the smooth normals).
+        vec3 norm = normalize(cross(dFdx(frag_position), dFdy(frag_position)));
+        color = light(norm) * frag_color_flat;
+    } else {
+        color = light(normalize(frag_normal)) * frag_color;
+    }
+
+    if (show_contours) {
+        color = mix(color, vec3(0.1, 0.05, 0.0), 0.6 * lines(frag_height, contour_interval));
+    }
+    color_out = vec4(overlay(color, frag_model), 1.0f);
 }
 "#;
 
 pub struct Terrain {
     vbo : VertexBuffer<Vertex>,
+    indices : IndexBuffer<u32>,
     program : Program,
+    shading : Shading,
+    spin : Spin,
+    contour_interval : f64,
+    height_range : f64,
 }
 
 impl Terrain {
-    pub fn new<F>(facade : &F) -> Result<Terrain, Box<Error>>
+    pub fn new<F>(facade : &F, config : &Config, mesh : &Mesh)
+        -> Result<Terrain, Box<Error>>
         where F : Facade
     {
-        let (mut v, i) = icosphere(5);
-        let per = noise::ScalePoint::new(noise::Perlin::new())
-            .set_all_scales(3.0, 3.0, 3.0, 1.0);
-        let curved = noise::Curve::new(&per)
-            .add_control_point(-2.0, -2.0)
-            .add_control_point(-1.0, -1.0)
-            .add_control_point(-0.5, -0.5)
-            .add_control_point( 0.0,  0.0)
-            .add_control_point( 0.6,  0.2)
-            .add_control_point( 1.0,  1.0);
-
-        let seed: &[_] = &[0];
-        let mut rng : ChaChaRng = SeedableRng::from_seed(seed);
-        let mut between = Range::new(-0.01, 0.01);
-        let mut jitter = || { between.sample(&mut rng) };
-
-        for i in 0..v.len() {
-            // Scale based on Perlin noise field
-            let offset = curved.get([v[i][0], v[i][1], v[i][2]]);
-            v[i] *= offset / 8.0 + 1.0;
-
-            // Add a little random jitter
-            v[i].x += jitter();
-            v[i].y += jitter();
-            v[i].z += jitter();
-        }
 
+        let array3f = |v : Vector3<f64>| { array3([v[0] as f32, v[1] as f32, v[2] as f32]) };
         let mut buffer : Vec<Vertex> = Vec::new();
-        i.iter().for_each(|tri| {
-            let a = v[tri[0]];
-            let b = v[tri[1]];
-            let c = v[tri[2]];
-
-            let array3f = |v : Vector3<f64>| { array3([v[0] as f32, v[1] as f32, v[2] as f32]) };
-
-            // Find the normal
-            let norm = array3f((b - a).cross(c - a).normalize());
-
-            // Biome colors (RGB)
-            let beach = [0.8, 0.7, 0.4];
-            let snow = [0.8, 0.8, 0.8];
-            let rock = [0.5, 0.4, 0.3];
-            let grass = [0.2, 0.6, 0.2];
-
-            let center = ((a + b + c) / 3.0).magnitude();
-            let color : Vector3<f64>;
-            let color =
-                if center < 1.005 {
-                    beach
-                } else if center < 1.03 {
-                    let mut g = grass;
-                    g[0] += 10f32 * jitter() as f32;
-                    g[1] += 10f32 * jitter() as f32;
-                    g[2] += 10f32 * jitter() as f32;
-                    g
-                } else if center < 1.08 {
-                    rock
-                } else {
-                    snow
-                };
-
-
-            // Store this triangle, with positions and per-vertex normals
-            buffer.push(Vertex { position : array3f(a), normal : norm, color : color });
-            buffer.push(Vertex { position : array3f(b), normal : norm, color : color });
-            buffer.push(Vertex { position : array3f(c), normal : norm, color : color });
+        let normals = mesh.normals();
+        for (i, pos) in mesh.verts.iter().enumerate() {
+            buffer.push(Vertex { position : array3f(*pos),
+                                 normal : array3f(normals[i]),
+                                 color : mesh.colors[i],
+                                 temperature : mesh.temperature[i] as f32,
+                                 height : (mesh.heights[i] - mesh.sea_level) as f32,
+                                 moisture : mesh.moisture[i] as f32,
+                                 biome : mesh.biomes[i] as f32 });
+        }
+        // Scales the false-color elevation view
+        let height_range = mesh.heights.iter()
+            .map(|h| { (h - mesh.sea_level).abs() })
+            .fold(1e-6, f64::max);
+
+        let mut indices : Vec<u32> = Vec::new();
+        mesh.tris.iter().for_each(|t| {
+            indices.push(t.x as u32);
+            indices.push(t.y as u32);
+            indices.push(t.z as u32);
         });
 
         let v = VertexBuffer::new(facade, &buffer)?;
+        let i = IndexBuffer::new(facade, PrimitiveType::TrianglesList,
+                                 &indices)?;
         let p = Program::from_source(facade, VERTEX_SHADER_SRC,
-                                     FRAGMENT_SHADER_SRC, None)?;
-        Ok(Terrain { vbo : v, program : p })
+                                     &with_debug(&with_overlays(FRAGMENT_SHADER_SRC)), None)?;
+        Ok(Terrain { vbo : v, indices : i, program : p,
+                     shading : config.shading, spin : config.spin.clone(),
+                     contour_interval : config.contour_interval,
+                     height_range : height_range })
     }
+}
 
-    pub fn draw(&self, mat : Matrix4<f32>, frame : &mut Frame, params : &DrawParameters) {
+impl Layer for Terrain {
+    fn draw(&self, ctx : &Context, frame : &mut Frame) {
 
-        let params = DrawParameters {
+        let params = wireframe(ctx.debug, DrawParameters {
             depth : Depth {
                 test: DepthTest::IfLess,
                 write: true,
                 .. Default::default()
             },
-            .. params.clone()
-        };
+            // The first vertex of each triangle supplies its flat color
+            provoking_vertex : ProvokingVertex::FirstVertex,
+            .. ctx.params.clone()
+        });
 
-        let indices = NoIndices(PrimitiveType::TrianglesList);
         let uniforms = uniform! {
-            M : array4x4(mat),
+            M : array4x4(ctx.mat),
+            flat_shading : self.shading == Shading::Flat,
+            sun : ctx.sun,
+            declination : self.spin.declination(ctx.counter as f64) as f32,
+            snow_line : self.spin.snow_line as f32,
+            seasonal_swing : self.spin.seasonal_swing as f32,
+            show_graticule : ctx.overlays.graticule,
+            show_contours : ctx.overlays.contours,
+            show_tropics : ctx.overlays.tropics,
+            contour_interval : self.contour_interval as f32,
+            axial_tilt : self.spin.axial_tilt as f32,
+            height_range : self.height_range as f32,
+            debug_view : DebugView::uniform(ctx.debug),
         };
 
-        frame.draw(&self.vbo, indices, &self.program,
+        frame.draw(&self.vbo, &self.indices, &self.program,
                    &uniforms, &params).unwrap();
     }
 }
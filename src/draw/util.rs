@@ -1,17 +1,35 @@
+use world::orbit;
+
 pub struct Interpolator {
     pub pts : Vec<[f32;2]>,
 }
 
 impl Interpolator {
     pub fn at(&self, input : f32) -> f32 {
-        for i in 0..(self.pts.len() - 1) {
-            if self.pts[i][0] <= input && input <= self.pts[i + 1][0] {
-                let frac = (input - self.pts[i][0]) /
-                           (self.pts[i + 1][0] - self.pts[i][0]);
-                return frac * (self.pts[i + 1][1] - self.pts[i][1])
-                       + self.pts[i][1];
+        // A single point is a constant
+        if self.pts.len() == 1 {
+            return self.pts[0][1];
+        }
+        for w in self.pts.windows(2) {
+            if w[0][0] <= input && input <= w[1][0] {
+                let frac = (input - w[0][0]) / (w[1][0] - w[0][0]);
+                return frac * (w[1][1] - w[0][1]) + w[0][1];
             }
         }
         return 0f32;
     }
 }
+
+/// Direction towards the star, as a shader uniform
+pub fn sun() -> [f32; 3] {
+    let s = orbit::sun();
+    [s.x as f32, s.y as f32, s.z as f32]
+}
+
+/// Inserts `text` into a shader after its `#version` line, so that
+/// shared uniforms and functions can be spliced into several shaders
+pub fn after_version(src : &str, text : &str) -> String {
+    let src = src.trim_start();
+    let split = src.find('\n').unwrap_or(src.len());
+    format!("{}\n{}{}", &src[..split], text, &src[split..])
+}
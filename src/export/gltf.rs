@@ -0,0 +1,319 @@
+extern crate serde_json;
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use export::{Part, clamp_color, write_f32, write_u32};
+
+////////////////////////////////////////////////////////////////////////////////
+
+const FLOAT : u32 = 5126;
+const UNSIGNED_INT : u32 = 5125;
+const ARRAY_BUFFER : u32 = 34962;
+const ELEMENT_ARRAY_BUFFER : u32 = 34963;
+const TRIANGLES : u32 = 4;
+
+#[derive(Serialize)]
+struct Asset {
+    version : String,
+    generator : String,
+}
+
+#[derive(Serialize)]
+struct Buffer {
+    #[serde(rename = "byteLength")]
+    byte_length : usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uri : Option<String>,
+}
+
+#[derive(Serialize)]
+struct BufferView {
+    buffer : usize,
+    #[serde(rename = "byteOffset")]
+    byte_offset : usize,
+    #[serde(rename = "byteLength")]
+    byte_length : usize,
+    target : u32,
+}
+
+#[derive(Serialize)]
+struct Accessor {
+    #[serde(rename = "bufferView")]
+    buffer_view : usize,
+    #[serde(rename = "componentType")]
+    component_type : u32,
+    count : usize,
+    #[serde(rename = "type")]
+    kind : String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min : Option<Vec<f32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max : Option<Vec<f32>>,
+}
+
+#[derive(Serialize)]
+struct Primitive {
+    attributes : BTreeMap<String, usize>,
+    indices : usize,
+    mode : u32,
+}
+
+#[derive(Serialize)]
+struct Mesh {
+    name : String,
+    primitives : Vec<Primitive>,
+}
+
+#[derive(Serialize)]
+struct Node {
+    name : String,
+    mesh : usize,
+}
+
+#[derive(Serialize)]
+struct Scene {
+    nodes : Vec<usize>,
+}
+
+#[derive(Serialize)]
+struct Root {
+    asset : Asset,
+    scene : usize,
+    scenes : Vec<Scene>,
+    nodes : Vec<Node>,
+    meshes : Vec<Mesh>,
+    accessors : Vec<Accessor>,
+    #[serde(rename = "bufferViews")]
+    buffer_views : Vec<BufferView>,
+    buffers : Vec<Buffer>,
+    extras : Extras,
+}
+
+#[derive(Serialize)]
+struct Extras {
+    /// Names for the values in the terrain's `_BIOME` attribute
+    biomes : Vec<String>,
+}
+
+/// Accumulates binary data and the views and accessors that describe it
+struct Builder {
+    bin : Vec<u8>,
+    views : Vec<BufferView>,
+    accessors : Vec<Accessor>,
+}
+
+impl Builder {
+    /// Appends a block of data and returns its accessor index
+    fn push(&mut self, data : Vec<u8>, target : u32, component_type : u32,
+            count : usize, kind : &str,
+            bounds : Option<(Vec<f32>, Vec<f32>)>) -> usize {
+        // Every component is four bytes, so blocks stay aligned
+        self.views.push(BufferView { buffer : 0, byte_offset : self.bin.len(),
+                                     byte_length : data.len(), target : target });
+        self.bin.extend(data);
+        let (min, max) = match bounds {
+            Some((a, b)) => (Some(a), Some(b)),
+            None => (None, None),
+        };
+        self.accessors.push(Accessor {
+            buffer_view : self.views.len() - 1, component_type : component_type,
+            count : count, kind : kind.to_string(), min : min, max : max });
+        self.accessors.len() - 1
+    }
+
+    fn vec3(&mut self, data : &[[f32; 3]], bounds : bool) -> Result<usize, Box<Error>> {
+        let mut bytes = Vec::new();
+        for v in data.iter().flat_map(|v| { v.iter() }) {
+            write_f32(&mut bytes, *v)?;
+        }
+        // glTF requires bounds on POSITION accessors
+        let bounds = if bounds {
+            let mut min = vec![::std::f32::INFINITY; 3];
+            let mut max = vec![::std::f32::NEG_INFINITY; 3];
+            for v in data {
+                for i in 0..3 {
+                    min[i] = min[i].min(v[i]);
+                    max[i] = max[i].max(v[i]);
+                }
+            }
+            Some((min, max))
+        } else {
+            None
+        };
+        Ok(self.push(bytes, ARRAY_BUFFER, FLOAT, data.len(), "VEC3", bounds))
+    }
+
+    /// Stores integers as floats, since a vertex attribute can't be
+    /// UNSIGNED_INT, and a tightly-packed UNSIGNED_SHORT would break
+    /// the four-byte alignment that attributes need
+    fn floats(&mut self, data : &[u32]) -> Result<usize, Box<Error>> {
+        let mut bytes = Vec::new();
+        for v in data {
+            write_f32(&mut bytes, *v as f32)?;
+        }
+        Ok(self.push(bytes, ARRAY_BUFFER, FLOAT, data.len(), "SCALAR", None))
+    }
+
+    fn indices(&mut self, data : &[u32]) -> Result<usize, Box<Error>> {
+        let mut bytes = Vec::new();
+        for v in data {
+            write_u32(&mut bytes, *v)?;
+        }
+        Ok(self.push(bytes, ELEMENT_ARRAY_BUFFER, UNSIGNED_INT, data.len(), "SCALAR", None))
+    }
+}
+
+/// Writes parts as glTF 2.0, with one mesh and node per part.
+///
+/// A `.glb` path produces a single binary file; otherwise the JSON is
+/// written to `path` and the geometry to a `.bin` file beside it.
+/// Terrain biome indices are stored as floats in a custom `_BIOME`
+/// attribute.
+pub fn save(parts : &[Part], path : &Path) -> Result<(), Box<Error>> {
+    let mut b = Builder { bin : Vec::new(), views : Vec::new(), accessors : Vec::new() };
+    let mut meshes = Vec::new();
+    let mut biomes = Vec::new();
+    for part in parts {
+        let mut attributes = BTreeMap::new();
+        attributes.insert("POSITION".to_string(), b.vec3(&part.positions, true)?);
+        attributes.insert("NORMAL".to_string(), b.vec3(&part.normals, false)?);
+        // COLOR_0 must stay within [0, 1]
+        let colors : Vec<[f32; 3]> = part.colors.iter()
+            .map(|c| { clamp_color(*c) })
+            .collect();
+        attributes.insert("COLOR_0".to_string(), b.vec3(&colors, false)?);
+        if let Some(ref ids) = part.biomes {
+            attributes.insert("_BIOME".to_string(), b.floats(ids)?);
+            biomes = part.biome_names.clone();
+        }
+        let tris : Vec<u32> = part.tris.iter().flat_map(|t| { t.iter().cloned() }).collect();
+        let indices = b.indices(&tris)?;
+        meshes.push(Mesh {
+            name : part.name.clone(),
+            primitives : vec![Primitive { attributes : attributes, indices : indices,
+                                          mode : TRIANGLES }],
+        });
+    }
+
+    let glb = path.extension().map(|e| { e == "glb" }).unwrap_or(false);
+    let bin_path = path.with_extension("bin");
+    let uri = if glb { None } else {
+        bin_path.file_name().map(|f| { f.to_string_lossy().into_owned() })
+    };
+    let root = Root {
+        asset : Asset { version : "2.0".to_string(),
+                        generator : "tiny-planets-rs".to_string() },
+        scene : 0,
+        scenes : vec![Scene { nodes : (0..parts.len()).collect() }],
+        nodes : parts.iter().enumerate()
+            .map(|(i, p)| { Node { name : p.name.clone(), mesh : i } })
+            .collect(),
+        meshes : meshes,
+        accessors : b.accessors,
+        buffer_views : b.views,
+        buffers : vec![Buffer { byte_length : b.bin.len(), uri : uri }],
+        extras : Extras { biomes : biomes },
+    };
+    let mut json = serde_json::to_vec(&root)?;
+
+    if !glb {
+        File::create(path)?.write_all(&json)?;
+        File::create(bin_path)?.write_all(&b.bin)?;
+        return Ok(());
+    }
+
+    // Chunks must be padded to four bytes: JSON with spaces, BIN with zeros
+    while json.len() % 4 != 0 {
+        json.push(b' ');
+    }
+    let mut bin = b.bin;
+    while bin.len() % 4 != 0 {
+        bin.push(0);
+    }
+
+    let mut w = BufWriter::new(File::create(path)?);
+    write_u32(&mut w, 0x46546C67)?; // "glTF"
+    write_u32(&mut w, 2)?;
+    write_u32(&mut w, (12 + 8 + json.len() + 8 + bin.len()) as u32)?;
+    write_u32(&mut w, json.len() as u32)?;
+    write_u32(&mut w, 0x4E4F534A)?; // "JSON"
+    w.write_all(&json)?;
+    write_u32(&mut w, bin.len() as u32)?;
+    write_u32(&mut w, 0x004E4942)?; // "BIN"
+    w.write_all(&bin)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use self::serde_json::Value;
+    use export::tests::{parts, temp};
+
+    fn u32_at(bytes : &[u8], i : usize) -> u32 {
+        bytes[i] as u32 | (bytes[i + 1] as u32) << 8
+            | (bytes[i + 2] as u32) << 16 | (bytes[i + 3] as u32) << 24
+    }
+
+    /// Reads every component of an accessor as a float or integer
+    fn read(json : &Value, bin : &[u8], accessor : u64) -> Vec<f64> {
+        let a = &json["accessors"][accessor as usize];
+        let view = &json["bufferViews"][a["bufferView"].as_u64().unwrap() as usize];
+        let start = view["byteOffset"].as_u64().unwrap() as usize;
+        let len = view["byteLength"].as_u64().unwrap() as usize;
+        bin[start..start + len].chunks(4).map(|c| {
+            let v = u32_at(c, 0);
+            if a["componentType"] == FLOAT { f32::from_bits(v) as f64 } else { v as f64 }
+        }).collect()
+    }
+
+    #[test]
+    fn glb_chunks_and_accessors() {
+        let parts = parts();
+        let path = temp("test.glb");
+        save(&parts, &path).unwrap();
+        let bytes = fs::read(&path).unwrap();
+
+        assert_eq!(u32_at(&bytes, 0), 0x46546C67);
+        assert_eq!(u32_at(&bytes, 4), 2);
+        assert_eq!(u32_at(&bytes, 8) as usize, bytes.len());
+        let json_len = u32_at(&bytes, 12) as usize;
+        assert_eq!(json_len % 4, 0);
+        let json : Value = serde_json::from_slice(&bytes[20..20 + json_len]).unwrap();
+        let bin = &bytes[20 + json_len + 8..];
+        assert_eq!(u32_at(&bytes, 20 + json_len) as usize, bin.len());
+        assert!(json["buffers"][0]["byteLength"].as_u64().unwrap() as usize <= bin.len());
+
+        for (part, mesh) in parts.iter().zip(json["meshes"].as_array().unwrap()) {
+            let p = &mesh["primitives"][0];
+            let count = |name : &str| {
+                json["accessors"][p["attributes"][name].as_u64().unwrap() as usize]["count"]
+                    .as_u64().unwrap() as usize };
+            assert_eq!(count("POSITION"), part.positions.len());
+            assert_eq!(count("NORMAL"), part.positions.len());
+
+            let colors = read(&json, bin, p["attributes"]["COLOR_0"].as_u64().unwrap());
+            assert!(colors.iter().all(|c| { *c >= 0.0 && *c <= 1.0 }));
+            let indices = read(&json, bin, p["indices"].as_u64().unwrap());
+            assert_eq!(indices.len(), part.tris.len() * 3);
+            assert!(indices.iter().all(|i| { (*i as usize) < part.positions.len() }));
+        }
+        assert_eq!(json["extras"]["biomes"].as_array().unwrap().len(),
+                   parts[0].biome_names.len());
+    }
+
+    #[test]
+    fn gltf_writes_a_matching_bin() {
+        let path = temp("test.gltf");
+        save(&parts(), &path).unwrap();
+        let json : Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        let bin = fs::read(path.with_extension("bin")).unwrap();
+        assert_eq!(json["buffers"][0]["byteLength"].as_u64().unwrap() as usize, bin.len());
+        assert_eq!(json["buffers"][0]["uri"], "tiny-planets-export-test.bin");
+    }
+}
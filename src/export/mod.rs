@@ -0,0 +1,186 @@
+extern crate cgmath;
+
+pub mod gltf;
+pub mod obj;
+pub mod ply;
+pub mod stl;
+
+use std::error::Error;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+use config::Config;
+use draw::clouds::{puffs, PUFF_RADIUS};
+use draw::icosphere::icosphere;
+use draw::ocean;
+use world::gas_giant::GasGiant;
+use world::decimate::decimate;
+use world::mesh::Mesh;
+
+use self::cgmath::{Vector3, InnerSpace};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// One named piece of exported geometry.
+///
+/// Triangles wind counter-clockwise when seen from outside the planet
+/// and normals point outwards, which is the reverse of the renderer.
+pub struct Part {
+    pub name : String,
+    pub positions : Vec<[f32; 3]>,
+    pub normals : Vec<[f32; 3]>,
+    pub colors : Vec<[f32; 3]>,
+    /// Index into `biome_names` at each vertex, for terrain only
+    pub biomes : Option<Vec<u32>>,
+    pub biome_names : Vec<String>,
+    pub tris : Vec<[u32; 3]>,
+}
+
+fn array3f(v : Vector3<f64>) -> [f32; 3] {
+    [v.x as f32, v.y as f32, v.z as f32]
+}
+
+/// Flips the renderer's inward-facing triangles
+fn outward(tris : &[Vector3<usize>]) -> Vec<[u32; 3]> {
+    tris.iter().map(|t| { [t.x as u32, t.z as u32, t.y as u32] }).collect()
+}
+
+/// The terrain mesh, as drawn by `Terrain`
+pub fn terrain(config : &Config, mesh : &Mesh) -> Part {
+    Part {
+        name : "terrain".to_string(),
+        positions : mesh.verts.iter().map(|v| { array3f(*v) }).collect(),
+        normals : mesh.normals().iter().map(|n| { array3f(-*n) }).collect(),
+        colors : mesh.colors.clone(),
+        biomes : Some(mesh.biomes.iter().map(|b| { *b as u32 }).collect()),
+        biome_names : config.biomes.table.iter().map(|b| { b.name.clone() }).collect(),
+        tris : outward(&mesh.tris),
+    }
+}
+
+/// The ocean sphere, as drawn by `Ocean`, in a single color
+pub fn ocean(color : [f32; 3], sea_level : f64) -> Part {
+    let (v, i) = ocean::sphere(sea_level);
+    Part {
+        name : "ocean".to_string(),
+        positions : v.iter().map(|v| { array3f(*v) }).collect(),
+        normals : v.iter().map(|v| { array3f(v.normalize()) }).collect(),
+        colors : vec![color; v.len()],
+        biomes : None,
+        biome_names : Vec::new(),
+        tris : outward(&i),
+    }
+}
+
+/// Cloud puffs, as small white icosahedra in place of the billboards
+/// drawn by `Clouds`
+pub fn clouds(config : &Config) -> Part {
+    let (v, i) = icosphere(0);
+    let mut part = Part {
+        name : "clouds".to_string(),
+        positions : Vec::new(), normals : Vec::new(), colors : Vec::new(),
+        biomes : None, biome_names : Vec::new(), tris : Vec::new(),
+    };
+    for p in puffs(config) {
+        let offset = part.positions.len() as u32;
+        for n in v.iter().map(|v| { v.normalize() }) {
+            let q = Vector3::new(p.x as f64, p.y as f64, p.z as f64)
+                  + n * PUFF_RADIUS as f64;
+            part.positions.push(array3f(q));
+            part.normals.push(array3f(n));
+            part.colors.push([1.0, 1.0, 1.0]);
+        }
+        for t in outward(&i) {
+            part.tris.push([t[0] + offset, t[1] + offset, t[2] + offset]);
+        }
+    }
+    part
+}
+
+/// A gas giant's cloud bands, sampled onto an icosphere without the
+/// renderer's band shear and limb darkening
+pub fn gas_giant(params : &GasGiant) -> Result<Part, Box<Error>> {
+    let (v, i) = icosphere(5);
+    let turbulence = params.turbulence.compile()?;
+    Ok(Part {
+        name : "gas_giant".to_string(),
+        positions : v.iter().map(|v| { array3f(*v) }).collect(),
+        normals : v.iter().map(|v| { array3f(v.normalize()) }).collect(),
+        colors : v.iter().map(|v| { params.color(v.normalize(), &*turbulence) }).collect(),
+        biomes : None,
+        biome_names : Vec::new(),
+        tris : outward(&i),
+    })
+}
+
+/// Builds every part that the viewer would draw for a config,
+/// optionally simplifying the terrain to about `triangles` triangles
+pub fn parts(config : &Config, triangles : Option<usize>) -> Result<Vec<Part>, Box<Error>> {
+    if let Some(ref g) = config.gas_giant {
+        return Ok(vec![gas_giant(g)?]);
+    }
+    let mesh = Mesh::generate(config);
+    let mut out = vec![match triangles {
+        Some(n) => terrain(config, &decimate(&mesh, n)),
+        None => terrain(config, &mesh),
+    }];
+    if let Some(color) = config.ocean {
+        out.push(ocean(color, mesh.sea_level));
+    }
+    if config.cloud_coverage > 0.0 {
+        out.push(clouds(config));
+    }
+    Ok(out)
+}
+
+/// Writes parts to a file, picking the format from its extension
+/// (`.obj`, `.ply`, `.gltf`, or `.glb`)
+pub fn save<P : AsRef<Path>>(parts : &[Part], path : P) -> Result<(), Box<Error>> {
+    let path = path.as_ref();
+    match path.extension().and_then(|e| { e.to_str() }) {
+        Some("obj") => obj::save(parts, path),
+        Some("ply") => ply::save(parts, path),
+        Some("gltf") | Some("glb") => gltf::save(parts, path),
+        _ => Err(From::from(format!("Unknown export format for '{}'",
+                                    path.display()))),
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Clamps a vertex color into [0, 1], since biome jitter can overshoot
+pub fn clamp_color(c : [f32; 3]) -> [f32; 3] {
+    [c[0].max(0.0).min(1.0), c[1].max(0.0).min(1.0), c[2].max(0.0).min(1.0)]
+}
+
+/// Writes a little-endian `u32`
+pub fn write_u32<W : Write>(w : &mut W, v : u32) -> io::Result<()> {
+    w.write_all(&[v as u8, (v >> 8) as u8, (v >> 16) as u8, (v >> 24) as u8])
+}
+
+/// Writes a little-endian `f32`
+pub fn write_f32<W : Write>(w : &mut W, v : f32) -> io::Result<()> {
+    write_u32(w, v.to_bits())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::env;
+    use std::path::PathBuf;
+
+    /// A small terrain and ocean, with one color pushed out of range
+    pub fn parts() -> Vec<Part> {
+        let config = Config { level : 2, .. Config::default() };
+        let mesh = Mesh::generate(&config);
+        let mut t = terrain(&config, &mesh);
+        t.colors[0] = [1.5, -0.2, 0.5];
+        vec![t, ocean([0.1, 0.2, 0.6], mesh.sea_level)]
+    }
+
+    /// A path in the temporary directory, unique to one test
+    pub fn temp(name : &str) -> PathBuf {
+        env::temp_dir().join(format!("tiny-planets-export-{}", name))
+    }
+}
@@ -0,0 +1,90 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use export::{Part, clamp_color};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Writes parts as a Wavefront OBJ file, with one object per part.
+///
+/// Vertex colors use the common `v x y z r g b` extension, and terrain
+/// faces are grouped by the biome of their first vertex.
+pub fn save(parts : &[Part], path : &Path) -> Result<(), Box<Error>> {
+    let mut w = BufWriter::new(File::create(path)?);
+    writeln!(w, "# tiny-planets-rs")?;
+
+    // OBJ indices are 1-based and global across the whole file
+    let mut offset = 1;
+    for part in parts {
+        writeln!(w, "o {}", part.name)?;
+        for (p, c) in part.positions.iter().zip(part.colors.iter()) {
+            let c = clamp_color(*c);
+            writeln!(w, "v {} {} {} {} {} {}", p[0], p[1], p[2], c[0], c[1], c[2])?;
+        }
+        for n in part.normals.iter() {
+            writeln!(w, "vn {} {} {}", n[0], n[1], n[2])?;
+        }
+
+        let base = offset;
+        let face = move |w : &mut BufWriter<File>, t : &[u32; 3]| {
+            let (a, b, c) = (t[0] + base, t[1] + base, t[2] + base);
+            writeln!(w, "f {}//{} {}//{} {}//{}", a, a, b, b, c, c)
+        };
+        match part.biomes {
+            Some(ref biomes) => for (i, name) in part.biome_names.iter().enumerate() {
+                let tris : Vec<&[u32; 3]> = part.tris.iter()
+                    .filter(|t| { biomes[t[0] as usize] as usize == i })
+                    .collect();
+                if tris.is_empty() {
+                    continue;
+                }
+                writeln!(w, "g {}_{}", part.name, name.replace(' ', "_"))?;
+                for t in tris {
+                    face(&mut w, t)?;
+                }
+            },
+            None => for t in part.tris.iter() {
+                face(&mut w, t)?;
+            },
+        }
+        offset += part.positions.len() as u32;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use export::tests::{parts, temp};
+
+    #[test]
+    fn vertices_faces_and_colors() {
+        let parts = parts();
+        let path = temp("test.obj");
+        save(&parts, &path).unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+
+        let verts : usize = parts.iter().map(|p| { p.positions.len() }).sum();
+        let tris : usize = parts.iter().map(|p| { p.tris.len() }).sum();
+        let lines = |prefix : &str| {
+            text.lines().filter(|l| { l.starts_with(prefix) }).count() };
+        assert_eq!(lines("o "), parts.len());
+        assert_eq!(lines("v "), verts);
+        assert_eq!(lines("vn "), verts);
+        assert_eq!(lines("f "), tris);
+
+        for l in text.lines().filter(|l| { l.starts_with("v ") }) {
+            let v : Vec<f32> = l[2..].split(' ').map(|s| { s.parse().unwrap() }).collect();
+            assert!(v[3..].iter().all(|c| { *c >= 0.0 && *c <= 1.0 }), "{}", l);
+        }
+        for l in text.lines().filter(|l| { l.starts_with("f ") }) {
+            for i in l[2..].split(|c| { c == ' ' || c == '/' }).filter(|s| { !s.is_empty() }) {
+                let i : usize = i.parse().unwrap();
+                assert!(i >= 1 && i <= verts, "{}", l);
+            }
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use export::{Part, clamp_color, write_f32, write_u32};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Writes parts as a single binary little-endian PLY mesh.
+///
+/// Each vertex has a position, normal, 8-bit color, the index of the
+/// part it came from, and a biome index (-1 outside the terrain).
+pub fn save(parts : &[Part], path : &Path) -> Result<(), Box<Error>> {
+    let mut w = BufWriter::new(File::create(path)?);
+
+    let verts : usize = parts.iter().map(|p| { p.positions.len() }).sum();
+    let faces : usize = parts.iter().map(|p| { p.tris.len() }).sum();
+    writeln!(w, "ply")?;
+    writeln!(w, "format binary_little_endian 1.0")?;
+    writeln!(w, "comment tiny-planets-rs")?;
+    for (i, part) in parts.iter().enumerate() {
+        writeln!(w, "comment part {} {}", i, part.name)?;
+    }
+    if let Some(part) = parts.iter().find(|p| { p.biomes.is_some() }) {
+        for (i, name) in part.biome_names.iter().enumerate() {
+            writeln!(w, "comment biome {} {}", i, name)?;
+        }
+    }
+    writeln!(w, "element vertex {}", verts)?;
+    for p in ["x", "y", "z", "nx", "ny", "nz"].iter() {
+        writeln!(w, "property float {}", p)?;
+    }
+    for p in ["red", "green", "blue", "part"].iter() {
+        writeln!(w, "property uchar {}", p)?;
+    }
+    writeln!(w, "property int biome")?;
+    writeln!(w, "element face {}", faces)?;
+    writeln!(w, "property list uchar uint vertex_indices")?;
+    writeln!(w, "end_header")?;
+
+    let byte = |c : f32| { (c * 255.0).round() as u8 };
+    for (i, part) in parts.iter().enumerate() {
+        for j in 0..part.positions.len() {
+            for v in part.positions[j].iter().chain(part.normals[j].iter()) {
+                write_f32(&mut w, *v)?;
+            }
+            let c = clamp_color(part.colors[j]);
+            w.write_all(&[byte(c[0]), byte(c[1]), byte(c[2]), i as u8])?;
+            let biome = part.biomes.as_ref().map(|b| { b[j] as i32 }).unwrap_or(-1);
+            write_u32(&mut w, biome as u32)?;
+        }
+    }
+
+    let mut offset = 0;
+    for part in parts {
+        for t in part.tris.iter() {
+            w.write_all(&[3])?;
+            for v in t.iter() {
+                write_u32(&mut w, v + offset)?;
+            }
+        }
+        offset += part.positions.len() as u32;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use export::tests::{parts, temp};
+
+    #[test]
+    fn header_matches_body() {
+        let parts = parts();
+        let path = temp("test.ply");
+        save(&parts, &path).unwrap();
+        let bytes = fs::read(&path).unwrap();
+
+        let end = b"end_header\n";
+        let split = bytes.windows(end.len()).position(|w| { w == end }).unwrap() + end.len();
+        let header = String::from_utf8(bytes[..split].to_vec()).unwrap();
+        let count = |element : &str| -> usize {
+            header.lines()
+                .find(|l| { l.starts_with(&format!("element {} ", element)) })
+                .and_then(|l| { l.split(' ').nth(2) })
+                .unwrap().parse().unwrap()
+        };
+        let verts : usize = parts.iter().map(|p| { p.positions.len() }).sum();
+        let tris : usize = parts.iter().map(|p| { p.tris.len() }).sum();
+        assert_eq!(count("vertex"), verts);
+        assert_eq!(count("face"), tris);
+
+        // Six floats, four color / part bytes, and a biome per vertex;
+        // a count byte and three indices per face
+        let body = &bytes[split..];
+        let stride = 6 * 4 + 4 + 4;
+        assert_eq!(body.len(), verts * stride + tris * (1 + 3 * 4));
+
+        // The first terrain vertex had its color clamped
+        assert_eq!(&body[24..28], &[255, 0, 128, 0]);
+        for f in body[verts * stride..].chunks(13) {
+            assert_eq!(f[0], 3);
+            for i in f[1..].chunks(4) {
+                let i = i[0] as usize | (i[1] as usize) << 8
+                      | (i[2] as usize) << 16 | (i[3] as usize) << 24;
+                assert!(i < verts);
+            }
+        }
+    }
+}
@@ -0,0 +1,187 @@
+extern crate cgmath;
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::f64::consts::PI;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use config::Config;
+use draw::icosphere::icosphere;
+use export::{write_f32, write_u32};
+use world::mesh::Mesh;
+
+use self::cgmath::{Vector3, InnerSpace};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Settings for a printable model
+#[derive(Clone, Debug)]
+pub struct PrintParams {
+    /// Diameter of the printed planet, in millimetres
+    pub diameter : f64,
+    /// Multiplier for height above sea level
+    pub exaggeration : f64,
+}
+
+impl Default for PrintParams {
+    fn default() -> PrintParams {
+        PrintParams { diameter : 50.0, exaggeration : 1.0 }
+    }
+}
+
+/// A closed triangle mesh, in millimetres, with outward-facing triangles
+pub struct Solid {
+    pub verts : Vec<Vector3<f64>>,
+    pub tris : Vec<[usize; 3]>,
+}
+
+impl Solid {
+    /// Builds a single solid from the terrain, with the ocean flattened
+    /// to sea level.
+    ///
+    /// Every vertex is pushed out along its (unjittered) icosphere
+    /// direction, so the solid is star-shaped around its center.
+    pub fn new(config : &Config, mesh : &Mesh, params : &PrintParams) -> Solid {
+        let (dirs, tris) = icosphere(config.level);
+        let sea = mesh.sea_level;
+        let radii : Vec<f64> = mesh.heights.iter().map(|h| {
+            (1.0 + sea + (h.max(sea) - sea) * params.exaggeration).max(0.01)
+        }).collect();
+
+        let max = radii.iter().cloned().fold(0.0, f64::max);
+        let scale = params.diameter / 2.0 / max;
+        Solid {
+            verts : dirs.iter().zip(radii.iter())
+                .map(|(d, r)| { d.normalize() * (r * scale) })
+                .collect(),
+            // Icosphere triangles wind inwards, so flip them
+            tris : tris.iter().map(|t| { [t.x, t.z, t.y] }).collect(),
+        }
+    }
+
+    /// Checks that the solid is safe to print.
+    ///
+    /// Every edge must be shared by exactly two triangles with opposite
+    /// windings (closed and manifold).  Every triangle must also face
+    /// away from the center, and their solid angles must add up to a
+    /// single sphere; together with star-shapedness, this means that no
+    /// two triangles can overlap (no self-intersections).
+    pub fn validate(&self) -> Result<(), Box<Error>> {
+        let mut edges : HashMap<(usize, usize), usize> = HashMap::new();
+        for t in self.tris.iter() {
+            for j in 0..3 {
+                *edges.entry((t[j], t[(j + 1) % 3])).or_insert(0) += 1;
+            }
+        }
+        for (&(a, b), &n) in edges.iter() {
+            if n != 1 || edges.get(&(b, a)) != Some(&1) {
+                return Err(From::from(format!(
+                    "Mesh is not closed and manifold at edge {}-{}", a, b)));
+            }
+        }
+
+        let mut total = 0.0;
+        for t in self.tris.iter() {
+            let (a, b, c) = (self.verts[t[0]], self.verts[t[1]], self.verts[t[2]]);
+            if a.dot(b.cross(c)) <= 0.0 {
+                return Err(From::from(format!(
+                    "Triangle {:?} faces inwards or is degenerate", t)));
+            }
+            let (a, b, c) = (a.normalize(), b.normalize(), c.normalize());
+            let num = a.dot(b.cross(c));
+            let den = 1.0 + a.dot(b) + b.dot(c) + c.dot(a);
+            total += 2.0 * num.atan2(den);
+        }
+        if (total - 4.0 * PI).abs() > 1e-6 {
+            return Err(From::from(format!(
+                "Triangles cover {:.6} steradians instead of 4 pi", total)));
+        }
+        Ok(())
+    }
+
+    /// Writes the solid as a binary STL file
+    pub fn save<P : AsRef<Path>>(&self, path : P) -> Result<(), Box<Error>> {
+        let mut w = BufWriter::new(File::create(path)?);
+        let mut header = [0u8; 80];
+        let name = b"tiny-planets-rs";
+        header[..name.len()].copy_from_slice(name);
+        w.write_all(&header)?;
+
+        write_u32(&mut w, self.tris.len() as u32)?;
+        for t in self.tris.iter() {
+            let (a, b, c) = (self.verts[t[0]], self.verts[t[1]], self.verts[t[2]]);
+            let n = (b - a).cross(c - a).normalize();
+            for v in [n, a, b, c].iter() {
+                write_f32(&mut w, v.x as f32)?;
+                write_f32(&mut w, v.y as f32)?;
+                write_f32(&mut w, v.z as f32)?;
+            }
+            w.write_all(&[0, 0])?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A unit octahedron with outward-facing triangles
+    fn octahedron() -> Solid {
+        let verts = vec![Vector3::new(1.0, 0.0, 0.0), Vector3::new(-1.0, 0.0, 0.0),
+                         Vector3::new(0.0, 1.0, 0.0), Vector3::new(0.0, -1.0, 0.0),
+                         Vector3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 0.0, -1.0)];
+        let mut tris = Vec::new();
+        for &x in [0, 1].iter() {
+            for &y in [2, 3].iter() {
+                for &z in [4, 5].iter() {
+                    // Odd numbers of negative axes flip the winding
+                    let flip = (x + y + z) % 2 == 1;
+                    tris.push(if flip { [x, z, y] } else { [x, y, z] });
+                }
+            }
+        }
+        Solid { verts : verts, tris : tris }
+    }
+
+    #[test]
+    fn octahedron_is_valid() {
+        octahedron().validate().unwrap();
+    }
+
+    #[test]
+    fn generated_solid_is_valid() {
+        let config = Config { level : 3, .. Config::default() };
+        let mesh = Mesh::generate(&config);
+        Solid::new(&config, &mesh, &PrintParams::default()).validate().unwrap();
+    }
+
+    #[test]
+    fn holes_are_rejected() {
+        let mut s = octahedron();
+        s.tris.pop();
+        assert!(s.validate().is_err());
+    }
+
+    #[test]
+    fn inward_triangles_are_rejected() {
+        let mut s = octahedron();
+        for t in s.tris.iter_mut() {
+            t.swap(1, 2);
+        }
+        assert!(s.validate().is_err());
+    }
+
+    #[test]
+    fn double_cover_is_rejected() {
+        // Two overlapping copies are each closed, but cover 8 pi
+        let mut s = octahedron();
+        let n = s.verts.len();
+        let copy : Vec<_> = s.tris.iter().map(|t| { [t[0] + n, t[1] + n, t[2] + n] }).collect();
+        s.verts = s.verts.iter().chain(s.verts.iter()).cloned().collect();
+        s.tris.extend(copy);
+        assert!(s.validate().is_err());
+    }
+}
@@ -44,6 +44,14 @@ impl Handle {
         frame.finish().unwrap();
     }
 
+    pub fn key(&mut self, c : char) {
+        self.lib.as_ref().map(|lib| {
+            let key :  Symbol<extern "C" fn(u32)> =
+                unsafe { lib.get(b"key\0").unwrap() };
+            key(c as u32);
+        });
+    }
+
     pub fn reload(&mut self, display : &glium::Display) {
         self.deinit();
         self.path.as_ref().map(fs::remove_file);
@@ -2,8 +2,15 @@
 // HERE BE DRAGONS
 ////////////////////////////////////////////////////////////////////////////////
 extern crate glium;
+extern crate serde;
+#[macro_use] extern crate serde_derive;
 
-mod draw;
+pub mod cli;
+pub mod config;
+pub mod draw;
+pub mod export;
+pub mod presets;
+pub mod world;
 
 static mut STATE : Option<draw::draw::State> = None;
 
@@ -21,6 +28,14 @@ pub extern "C" fn draw(counter : i32, frame : *mut glium::Frame) {
     }
 }
 
+#[no_mangle]
+pub extern "C" fn key(c : u32) {
+    unsafe {
+        STATE.as_mut().map(|s| {
+            std::char::from_u32(c).map(|c| { s.key(c) }) });
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn deinit() {
     unsafe {
@@ -5,6 +5,8 @@ extern crate notify;
 
 #[macro_use] extern crate objc;
 
+extern crate live;
+
 mod handle;
 
 use std::process::{Command, Child};
@@ -25,6 +27,16 @@ use notify::DebouncedEvent::{Write, Create};
 fn main() {
     use glium::glutin;
 
+    // Headless subcommands (e.g. 'stats') run without opening a window
+    let args : Vec<String> = std::env::args().collect();
+    if let Some(result) = live::cli::run(&args) {
+        if let Err(err) = result {
+            println!("Error: {}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let running = Arc::new(AtomicBool::new(true));
     let r = running.clone();
     ctrlc::set_handler(move || {
@@ -72,15 +84,22 @@ fn main() {
         handle.draw(counter, display.draw());
         counter += 1;
 
+        // Collect key presses, then pass them to the library once the
+        // event loop has released its borrow
+        let mut keys = Vec::new();
         events_loop.poll_events(|ev| {
             match ev {
                 glutin::Event::WindowEvent { event, .. } => match event {
                     glutin::WindowEvent::Closed => running.store(false, Ordering::SeqCst),
+                    glutin::WindowEvent::ReceivedCharacter(c) => keys.push(c),
                     _ => (),
                 },
                 _ => (),
             }
         });
+        for c in keys {
+            handle.key(c);
+        }
 
         match watch_rx.try_recv() {
            Ok(event) => {
@@ -0,0 +1,179 @@
+extern crate serde_json;
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::File;
+use std::path::Path;
+
+use config::{Config, Moon, SeaLevel};
+use world::biome::{Biome, Biomes};
+use world::craters::Craters;
+use world::elevation::Elevation;
+use world::gas_giant::GasGiant;
+use world::orbit::Orbit;
+use world::rings::Rings;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// The default green-and-blue planet
+pub fn earthlike() -> Config {
+    Config::default()
+}
+
+/// A dry, dusty planet with dunes and canyons
+pub fn desert() -> Config {
+    Config {
+        elevation : Elevation { continent_weight : 0.3, mountains : 0.3,
+                                .. Elevation::default() },
+        biomes : Biomes {
+            table : vec![
+                Biome { elevation : Some([0.06, 10.0]), jitter : 0.02,
+                        .. Biome::new("mesa", [0.55, 0.3, 0.2]) },
+                Biome { elevation : Some([-10.0, -0.02]), jitter : 0.03,
+                        .. Biome::new("basin", [0.7, 0.45, 0.3]) },
+                Biome { jitter : 0.05,
+                        .. Biome::new("dunes", [0.85, 0.6, 0.35]) },
+            ],
+            .. Biomes::default()
+        },
+        ocean : None,
+        cloud_coverage : 0.1,
+        atmosphere : Some([1.0, 0.7, 0.5]),
+        .. Config::default()
+    }
+}
+
+/// A frozen world with pale seas
+pub fn ice() -> Config {
+    Config {
+        biomes : Biomes {
+            table : vec![
+                Biome { elevation : Some([0.05, 10.0]), jitter : 0.02,
+                        .. Biome::new("glacier", [0.7, 0.8, 0.9]) },
+                Biome { moisture : Some([-10.0, 0.3]), jitter : 0.03,
+                        .. Biome::new("bare rock", [0.45, 0.45, 0.5]) },
+                Biome { jitter : 0.03,
+                        .. Biome::new("snow", [0.9, 0.92, 0.95]) },
+            ],
+            .. Biomes::default()
+        },
+        ocean : Some([0.3, 0.45, 0.55]),
+        cloud_coverage : 0.5,
+        atmosphere : Some([0.7, 0.85, 1.0]),
+        .. Config::default()
+    }
+}
+
+/// Black basalt crust floating on seas of molten rock
+pub fn lava() -> Config {
+    Config {
+        elevation : Elevation { mountains : 0.8, .. Elevation::default() },
+        biomes : Biomes {
+            table : vec![
+                Biome { elevation : Some([-10.0, 0.01]), jitter : 0.05,
+                        .. Biome::new("cooling crust", [0.5, 0.15, 0.05]) },
+                Biome { jitter : 0.03,
+                        .. Biome::new("basalt", [0.15, 0.12, 0.12]) },
+            ],
+            .. Biomes::default()
+        },
+        ocean : Some([0.9, 0.3, 0.05]),
+        cloud_coverage : 0.3,
+        atmosphere : Some([1.0, 0.4, 0.2]),
+        .. Config::default()
+    }
+}
+
+/// Scattered islands in a global ocean
+pub fn ocean() -> Config {
+    Config {
+        elevation : Elevation { amplitude : 0.06, continent_weight : 0.3,
+                                .. Elevation::default() },
+        sea_level : SeaLevel::LandFraction(0.08),
+        cloud_coverage : 1.5,
+        .. Config::default()
+    }
+}
+
+/// A cratered, airless moon
+pub fn moon() -> Config {
+    Config {
+        elevation : Elevation { amplitude : 0.03, mountains : 0.0,
+                                .. Elevation::default() },
+        craters : Some(Craters::default()),
+        biomes : Biomes {
+            table : vec![
+                Biome { elevation : Some([-10.0, -0.02]), jitter : 0.02,
+                        .. Biome::new("mare", [0.3, 0.3, 0.32]) },
+                Biome { jitter : 0.03,
+                        .. Biome::new("highlands", [0.6, 0.6, 0.58]) },
+            ],
+            .. Biomes::default()
+        },
+        ocean : None,
+        cloud_coverage : 0.0,
+        atmosphere : None,
+        star_cutoff : 0.6,
+        .. Config::default()
+    }
+}
+
+/// A banded gas giant with a great storm and two moons
+pub fn gas_giant() -> Config {
+    Config {
+        gas_giant : Some(GasGiant::default()),
+        rings : Some(Rings::default()),
+        atmosphere : Some([0.9, 0.8, 0.6]),
+        moons : vec![
+            Moon { config : Config { seed : 1, level : 4, .. moon() },
+                   orbit : Orbit { radius : 3.0, period : 1500.0, .. Orbit::default() },
+                   size : 0.2 },
+            Moon { config : Config { level : 4, .. ice() },
+                   orbit : Orbit { radius : 4.5, period : 3000.0, inclination : -8.0,
+                                   phase : 120.0 },
+                   size : 0.3 },
+        ],
+        .. Config::default()
+    }
+}
+
+/// Reads user-defined presets from a JSON object mapping names to configs
+pub fn from_file<P : AsRef<Path>>(path : P)
+    -> Result<BTreeMap<String, Config>, Box<Error>>
+{
+    let f = File::open(path)?;
+    let json : BTreeMap<String, serde_json::Value> = serde_json::from_reader(f)?;
+    let mut presets = BTreeMap::new();
+    for (name, json) in json {
+        let config = Config::from_json(json)
+            .map_err(|err| { format!("{}: {}", name, err) })?;
+        presets.insert(name, config);
+    }
+    Ok(presets)
+}
+
+/// Returns the built-in presets, followed by any from `presets.json`
+/// in the working directory (which may override built-in names).
+pub fn all() -> Vec<(String, Config)> {
+    let mut out : Vec<(String, Config)> = vec![
+        ("earthlike".to_string(), earthlike()),
+        ("desert".to_string(), desert()),
+        ("ice".to_string(), ice()),
+        ("lava".to_string(), lava()),
+        ("ocean".to_string(), ocean()),
+        ("moon".to_string(), moon()),
+        ("gas_giant".to_string(), gas_giant()),
+    ];
+
+    let path = "presets.json";
+    if Path::new(path).exists() {
+        match from_file(path) {
+            Ok(user) => for (name, config) in user {
+                out.retain(|p| { p.0 != name });
+                out.push((name, config));
+            },
+            Err(err) => println!("Couldn't load {}: {}", path, err),
+        }
+    }
+    out
+}
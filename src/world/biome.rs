@@ -0,0 +1,117 @@
+extern crate noise;
+
+use world::noise_graph::{NoiseGraph, Fractal};
+
+use self::noise::{NoiseFn, Constant};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// A single row in the biome table.
+///
+/// Each range is `[min, max]`; a missing range matches anything.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Biome {
+    pub name : String,
+    pub color : [f32; 3],
+
+    /// Amount of random per-vertex color variation
+    #[serde(default)]
+    pub jitter : f32,
+
+    /// Height above sea level
+    #[serde(default)]
+    pub elevation : Option<[f64; 2]>,
+    /// Roughly 1 at the equator and 0 at the poles, minus altitude cooling
+    #[serde(default)]
+    pub temperature : Option<[f64; 2]>,
+    /// 0 (arid) to 1 (wet)
+    #[serde(default)]
+    pub moisture : Option<[f64; 2]>,
+}
+
+fn within(range : &Option<[f64; 2]>, v : f64) -> bool {
+    range.map(|r| { r[0] <= v && v <= r[1] }).unwrap_or(true)
+}
+
+impl Biome {
+    pub fn new(name : &str, color : [f32; 3]) -> Biome {
+        Biome { name : name.to_string(), color : color, jitter : 0.0,
+                elevation : None, temperature : None, moisture : None }
+    }
+
+    pub fn matches(&self, elevation : f64, temperature : f64, moisture : f64) -> bool {
+        within(&self.elevation, elevation) &&
+        within(&self.temperature, temperature) &&
+        within(&self.moisture, moisture)
+    }
+}
+
+/// Parameters for assigning biomes to the terrain
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Biomes {
+    /// Moisture field, remapped from [-1, 1] to [0, 1]
+    pub moisture : NoiseGraph,
+
+    /// Temperature drop per unit of height above sea level, which the
+    /// climate model also uses when it's enabled
+    pub lapse_rate : f64,
+
+    /// Biomes are tested in order and the first match wins;
+    /// the last entry is used if nothing matches.  There must be at
+    /// least one entry.
+    pub table : Vec<Biome>,
+}
+
+impl Default for Biomes {
+    fn default() -> Biomes {
+        let moisture = NoiseGraph::Fbm(Fractal {
+            seed : 400, octaves : 4, frequency : 2.0, .. Default::default() });
+        Biomes {
+            moisture : moisture,
+            lapse_rate : 5.0,
+            table : vec![
+                Biome { temperature : Some([-10.0, 0.15]),
+                        .. Biome::new("ice", [0.85, 0.9, 0.95]) },
+                Biome { elevation : Some([-10.0, 0.005]),
+                        .. Biome::new("beach", [0.8, 0.7, 0.4]) },
+                Biome { elevation : Some([0.08, 10.0]),
+                        .. Biome::new("snow", [0.8, 0.8, 0.8]) },
+                Biome { elevation : Some([0.03, 10.0]),
+                        .. Biome::new("rock", [0.5, 0.4, 0.3]) },
+                Biome { temperature : Some([-10.0, 0.35]), jitter : 0.05,
+                        .. Biome::new("tundra", [0.45, 0.5, 0.4]) },
+                Biome { temperature : Some([0.6, 10.0]),
+                        moisture : Some([-10.0, 0.35]), jitter : 0.05,
+                        .. Biome::new("desert", [0.85, 0.65, 0.35]) },
+                Biome { moisture : Some([0.6, 10.0]), jitter : 0.05,
+                        .. Biome::new("forest", [0.1, 0.4, 0.15]) },
+                Biome { jitter : 0.1,
+                        .. Biome::new("grass", [0.2, 0.6, 0.2]) },
+            ],
+        }
+    }
+}
+
+impl Biomes {
+    /// Compiles the moisture field, or reports why it couldn't be
+    /// compiled and falls back to an even 0.5 everywhere
+    pub fn moisture_field(&self) -> Box<NoiseFn<[f64; 3]>> {
+        self.moisture.compile().unwrap_or_else(|err| {
+            println!("Couldn't construct moisture field: {}", err);
+            Box::new(Constant::new(0.0))
+        })
+    }
+
+    /// Returns the index of the matching biome in the table
+    pub fn classify(&self, elevation : f64, temperature : f64, moisture : f64) -> usize {
+        self.table.iter()
+            .position(|b| { b.matches(elevation, temperature, moisture) })
+            .unwrap_or(self.table.len() - 1)
+    }
+
+    /// Temperature from latitude (in radians) and height above sea level
+    pub fn temperature(&self, latitude : f64, elevation : f64) -> f64 {
+        latitude.cos() - self.lapse_rate * elevation.max(0.0)
+    }
+}
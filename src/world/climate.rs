@@ -0,0 +1,169 @@
+extern crate cgmath;
+extern crate image;
+
+use std::error::Error;
+use std::f64::consts::PI;
+
+use world::mesh::Mesh;
+use world::orbit::Spin;
+use world::sphere::{latitude, east, equirect, Nearest};
+
+use self::cgmath::{Vector3, InnerSpace};
+use self::image::ImageBuffer;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Parameters for the climate model
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClimateParams {
+    /// Number of moisture transport steps; this should be large enough
+    /// for wind to carry moisture across a continent.
+    pub iterations : usize,
+    /// Fraction of carried moisture that falls as rain at each step
+    pub rain_rate : f64,
+    /// Extra rain per unit of slope when wind is forced uphill
+    pub orographic : f64,
+}
+
+impl Default for ClimateParams {
+    fn default() -> ClimateParams {
+        ClimateParams {
+            iterations : 200,
+            rain_rate : 0.02,
+            orographic : 2.0,
+        }
+    }
+}
+
+/// Per-vertex climate, on the same scales used by the biome table
+pub struct Climate {
+    /// Roughly 1 at the equator and 0 at the poles
+    pub temperature : Vec<f64>,
+    /// Normalized to [0, 1]
+    pub precipitation : Vec<f64>,
+}
+
+/// Annual mean of noon-time sun elevation, with the sun's declination
+/// swinging through +/- the axial tilt over a year
+fn insolation(lat : f64, tilt : f64) -> f64 {
+    let steps = 24;
+    (0..steps).map(|i| {
+        let decl = tilt * (2.0 * PI * i as f64 / steps as f64).sin();
+        (lat - decl).cos().max(0.0)
+    }).sum::<f64>() / steps as f64
+}
+
+/// Prevailing wind direction for a latitude band: trade winds blow
+/// towards the west below 30 degrees, westerlies above that, and
+/// polar easterlies beyond 60 degrees.
+fn wind(p : Vector3<f64>) -> Vector3<f64> {
+    let lat = latitude(p).abs().to_degrees();
+    if lat < 30.0 || lat > 60.0 { -east(p) } else { east(p) }
+}
+
+impl Climate {
+    /// Simulates the climate on the undisplaced mesh, with seasons set
+    /// by the body's axial tilt and cooling with height set by the
+    /// biome table's lapse rate
+    pub fn simulate(mesh : &Mesh, params : &ClimateParams, spin : &Spin,
+                    lapse_rate : f64) -> Climate {
+        let tilt = spin.axial_tilt.to_radians();
+        let n = mesh.verts.len();
+
+        let sea = mesh.sea_level;
+        let temperature = mesh.verts.iter().zip(mesh.heights.iter())
+            .map(|(p, h)| {
+                insolation(latitude(*p), tilt) - lapse_rate * (h - sea).max(0.0)
+            })
+            .collect();
+
+        // For each vertex, find the downwind neighbors and how much of
+        // the outgoing air each one receives, plus the rain that falls
+        // when the air is pushed over it.
+        let neighbors = mesh.neighbors();
+        let mut downwind : Vec<Vec<(usize, f64, f64)>> = Vec::with_capacity(n);
+        for i in 0..n {
+            let w = wind(mesh.verts[i]);
+            let mut out = Vec::new();
+            for &j in &neighbors[i] {
+                let d = mesh.verts[j] - mesh.verts[i];
+                let weight = d.normalize().dot(w);
+                if weight > 0.0 {
+                    let slope = (mesh.heights[j] - mesh.heights[i]).max(0.0)
+                              / d.magnitude();
+                    let rain = (params.rain_rate + params.orographic * slope).min(1.0);
+                    out.push((j, weight, rain));
+                }
+            }
+            let total : f64 = out.iter().map(|o| { o.1 }).sum();
+            for o in out.iter_mut() {
+                o.1 /= total;
+            }
+            downwind.push(out);
+        }
+
+        // Iteratively carry humidity downwind, with the ocean acting as
+        // an unlimited source of moisture.
+        let ocean : Vec<bool> = mesh.heights.iter().map(|h| { *h < sea }).collect();
+        let mut humidity = vec![0.0; n];
+        let mut precipitation = vec![0.0; n];
+        for _ in 0..params.iterations {
+            let mut next = vec![0.0; n];
+            let mut incoming = vec![0.0; n];
+            for i in 0..n {
+                let h = if ocean[i] { 1.0 } else { humidity[i] };
+                for &(j, weight, rain) in &downwind[i] {
+                    next[j] += weight * h * (1.0 - rain);
+                    incoming[j] += weight;
+                }
+            }
+            for i in 0..n {
+                humidity[i] = if incoming[i] > 0.0 { next[i] / incoming[i] }
+                              else { 0.0 };
+            }
+        }
+        for i in 0..n {
+            let h = if ocean[i] { 1.0 } else { humidity[i] };
+            precipitation[i] = downwind[i].iter()
+                .map(|&(_, weight, rain)| { weight * h * rain })
+                .sum();
+        }
+
+        // Normalize rainfall over land, so that it can drive biomes
+        let max = precipitation.iter().zip(ocean.iter())
+            .filter(|&(_, o)| { !o })
+            .map(|(p, _)| { *p })
+            .fold(0.0, f64::max);
+        if max > 0.0 {
+            for p in precipitation.iter_mut() {
+                *p = (*p / max).min(1.0);
+            }
+        }
+
+        Climate { temperature : temperature, precipitation : precipitation }
+    }
+
+    /// Saves temperature and precipitation as equirectangular grayscale
+    /// maps, named `{prefix}temperature.png` and `{prefix}precipitation.png`
+    pub fn save_maps(&self, mesh : &Mesh, prefix : &str, width : u32)
+        -> Result<(), Box<Error>>
+    {
+        let height = width / 2;
+        let nearest = Nearest::new(&mesh.verts);
+        let index : Vec<usize> = (0..width * height)
+            .map(|i| { nearest.find(equirect(i % width, i / width, width, height)) })
+            .collect();
+
+        let save = |values : &[f64], name : &str| -> Result<(), Box<Error>> {
+            let img = ImageBuffer::from_fn(width, height, |x, y| {
+                let v = values[index[(y * width + x) as usize]];
+                image::Luma([(v.max(0.0).min(1.0) * 255.0) as u8])
+            });
+            img.save(format!("{}{}.png", prefix, name))?;
+            Ok(())
+        };
+        save(&self.temperature, "temperature")?;
+        save(&self.precipitation, "precipitation")
+    }
+}
@@ -0,0 +1,114 @@
+extern crate cgmath;
+extern crate rand;
+
+use std::cmp::Ordering;
+
+use world::mesh::Mesh;
+
+use self::cgmath::{Vector3, InnerSpace};
+use self::rand::distributions::{Range, Sample};
+use self::rand::{SeedableRng, ChaChaRng};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Parameters for crater stamping
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Craters {
+    pub seed : u32,
+    pub count : usize,
+
+    /// Crater radii, in radians, are drawn from a power law between
+    /// these bounds, with N(> r) proportional to r^-exponent
+    pub min_radius : f64,
+    pub max_radius : f64,
+    pub exponent : f64,
+
+    /// Bowl depth and rim height, as fractions of the crater radius
+    pub depth : f64,
+    pub rim : f64,
+    /// Craters larger than this radius get a central peak
+    pub peak_radius : f64,
+    /// Ejecta blanket extent, as a multiple of the crater radius
+    pub ejecta : f64,
+}
+
+impl Default for Craters {
+    fn default() -> Craters {
+        Craters {
+            seed : 0,
+            count : 400,
+            min_radius : 0.02,
+            max_radius : 0.4,
+            exponent : 2.0,
+            depth : 0.2,
+            rim : 0.05,
+            peak_radius : 0.12,
+            ejecta : 2.5,
+        }
+    }
+}
+
+/// Stamps craters into the mesh's heights.
+///
+/// Craters are applied from largest to smallest, so younger (smaller)
+/// craters cut into the rims and floors of older ones.
+pub fn stamp(mesh : &mut Mesh, params : &Craters) {
+    let seed : &[_] = &[params.seed];
+    let mut rng : ChaChaRng = SeedableRng::from_seed(seed);
+    let mut between = Range::new(-1.0, 1.0);
+    let mut uniform = Range::new(0.0, 1.0);
+
+    let b = params.exponent;
+    let (lo, hi) = (params.min_radius.powf(-b), params.max_radius.powf(-b));
+    let mut craters : Vec<(Vector3<f64>, f64)> = (0..params.count).map(|_| {
+        let mut v = Vector3::new(1.0, 1.0, 1.0);
+        while v.magnitude() > 1.0 || v.magnitude() < 1e-3 {
+            v = Vector3::new(between.sample(&mut rng),
+                             between.sample(&mut rng),
+                             between.sample(&mut rng));
+        }
+        // Inverse CDF of the truncated power law
+        let u = uniform.sample(&mut rng);
+        (v.normalize(), (lo - u * (lo - hi)).powf(-1.0 / b))
+    }).collect();
+    craters.sort_by(|a, b| { b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal) });
+
+    for &(center, radius) in craters.iter() {
+        let depth = params.depth * radius;
+        let rim = params.rim * radius;
+        let reach = (radius * params.ejecta).cos();
+
+        // The floor is cut relative to the surface at the center, which
+        // erases whatever older features were there.
+        let base = mesh.verts.iter().zip(mesh.heights.iter())
+            .max_by(|a, b| { a.0.dot(center).partial_cmp(&b.0.dot(center))
+                                  .unwrap_or(Ordering::Equal) })
+            .map(|(_, h)| { *h })
+            .unwrap_or(0.0);
+
+        for (p, h) in mesh.verts.iter().zip(mesh.heights.iter_mut()) {
+            let c = p.normalize().dot(center);
+            if c < reach {
+                continue;
+            }
+            let x = c.min(1.0).acos() / radius;
+            if x < 1.0 {
+                let mut floor = base - depth + (depth + rim) * x * x;
+                if radius > params.peak_radius {
+                    floor += depth * 0.5 * (-(x / 0.15).powi(2)).exp();
+                }
+                // Blend into the rim to avoid a seam at the crater edge
+                let t = ((x - 0.8) / 0.2).max(0.0);
+                *h = floor * (1.0 - t) + (*h + rim) * t;
+            } else {
+                // Raised rim falling off into a thin ejecta blanket,
+                // which fades out to nothing at its outer edge
+                let fade = ((params.ejecta - x) / (params.ejecta - 1.0).max(1e-6))
+                    .max(0.0).min(1.0);
+                *h += (rim * (-((x - 1.0) / 0.2).powi(2)).exp()
+                    + rim * 0.3 * x.powi(-3)) * fade;
+            }
+        }
+    }
+}
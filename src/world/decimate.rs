@@ -0,0 +1,337 @@
+extern crate cgmath;
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use world::mesh::Mesh;
+
+use self::cgmath::{Vector3, InnerSpace};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Extra cost for moving a vertex that sits on a coastline or
+/// biome boundary, so that boundaries are simplified last
+const BOUNDARY_WEIGHT : f64 = 10.0;
+
+/// Symmetric 4x4 error quadric, stored as its upper triangle
+#[derive(Copy, Clone)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    /// Squared distance to the plane through `p` with unit normal `n`,
+    /// scaled by `w`
+    fn plane(n : Vector3<f64>, p : Vector3<f64>, w : f64) -> Quadric {
+        let d = -n.dot(p);
+        let (a, b, c) = (n.x, n.y, n.z);
+        Quadric([a*a*w, a*b*w, a*c*w, a*d*w,
+                 b*b*w, b*c*w, b*d*w,
+                 c*c*w, c*d*w,
+                 d*d*w])
+    }
+
+    fn add(&mut self, o : &Quadric) {
+        for i in 0..10 {
+            self.0[i] += o.0[i];
+        }
+    }
+
+    fn error(&self, p : Vector3<f64>) -> f64 {
+        let q = &self.0;
+        let (x, y, z) = (p.x, p.y, p.z);
+        q[0]*x*x + 2.0*q[1]*x*y + 2.0*q[2]*x*z + 2.0*q[3]*x
+                 + q[4]*y*y + 2.0*q[5]*y*z + 2.0*q[6]*y
+                 + q[7]*z*z + 2.0*q[8]*z
+                 + q[9]
+    }
+}
+
+/// A candidate collapse of vertex `from` onto vertex `to`
+struct Collapse {
+    cost : f64,
+    from : usize,
+    to : usize,
+    /// Versions of both vertices when the cost was computed
+    stamp : (usize, usize),
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other : &Collapse) -> bool { self.cost == other.cost }
+}
+impl Eq for Collapse {}
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other : &Collapse) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Collapse {
+    // Reversed, so that the heap pops the cheapest collapse first
+    fn cmp(&self, other : &Collapse) -> Ordering {
+        other.cost.partial_cmp(&self.cost).unwrap_or(Ordering::Equal)
+    }
+}
+
+struct Simplifier<'a> {
+    mesh : &'a Mesh,
+    tris : Vec<Option<[usize; 3]>>,
+    /// Live triangles around each vertex
+    faces : Vec<Vec<usize>>,
+    quadrics : Vec<Quadric>,
+    /// Land/sea and biome of each vertex
+    class : Vec<(bool, usize)>,
+    alive : Vec<bool>,
+    version : Vec<usize>,
+    heap : BinaryHeap<Collapse>,
+}
+
+impl<'a> Simplifier<'a> {
+    fn neighbors(&self, i : usize) -> Vec<usize> {
+        let mut out = Vec::new();
+        for &f in self.faces[i].iter() {
+            for &j in self.tris[f].unwrap().iter() {
+                if j != i && !out.contains(&j) {
+                    out.push(j);
+                }
+            }
+        }
+        out
+    }
+
+    /// Returns the set of classes touching a vertex, including its own
+    fn classes(&self, i : usize) -> Vec<(bool, usize)> {
+        let mut out = vec![self.class[i]];
+        for j in self.neighbors(i) {
+            if !out.contains(&self.class[j]) {
+                out.push(self.class[j]);
+            }
+        }
+        out
+    }
+
+    /// Checks whether `from` can collapse onto `to` without changing
+    /// topology, flipping triangles, or moving a boundary
+    fn allowed(&self, from : usize, to : usize) -> bool {
+        // A boundary vertex may only slide along its own boundary, onto
+        // a vertex that touches every class it touches.
+        if self.class[from] != self.class[to] {
+            return false;
+        }
+        let (cf, ct) = (self.classes(from), self.classes(to));
+        if !cf.iter().all(|c| { ct.contains(c) }) {
+            return false;
+        }
+
+        // Link condition: on a closed manifold, the edge's endpoints
+        // must share exactly the two vertices opposite the edge.
+        let nt = self.neighbors(to);
+        let shared = self.neighbors(from).iter().filter(|n| { nt.contains(*n) }).count();
+        if shared != 2 {
+            return false;
+        }
+
+        // Reject collapses that flip any remaining triangle
+        let v = &self.mesh.verts;
+        for &f in self.faces[from].iter() {
+            let t = self.tris[f].unwrap();
+            if t.contains(&to) {
+                continue;
+            }
+            let before = (v[t[1]] - v[t[0]]).cross(v[t[2]] - v[t[0]]);
+            let moved : Vec<Vector3<f64>> = t.iter()
+                .map(|&k| { if k == from { v[to] } else { v[k] } })
+                .collect();
+            let after = (moved[1] - moved[0]).cross(moved[2] - moved[0]);
+            if before.dot(after) <= 0.0 {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn push(&mut self, from : usize, to : usize) {
+        let mut q = self.quadrics[from];
+        q.add(&self.quadrics[to]);
+        let mut cost = q.error(self.mesh.verts[to]);
+        if self.classes(from).len() > 1 {
+            cost *= BOUNDARY_WEIGHT;
+        }
+        self.heap.push(Collapse { cost : cost, from : from, to : to,
+                                  stamp : (self.version[from], self.version[to]) });
+    }
+
+    /// Queues every collapse out of and into `i`
+    fn push_around(&mut self, i : usize) {
+        for j in self.neighbors(i) {
+            self.push(i, j);
+            self.push(j, i);
+        }
+    }
+
+    fn collapse(&mut self, from : usize, to : usize) {
+        let q = self.quadrics[from];
+        self.quadrics[to].add(&q);
+
+        for f in self.faces[from].clone() {
+            let t = self.tris[f].unwrap();
+            if t.contains(&to) {
+                // Degenerate after the collapse, so remove it everywhere
+                for &k in t.iter() {
+                    self.faces[k].retain(|g| { *g != f });
+                }
+                self.tris[f] = None;
+            } else {
+                let mut t = t;
+                for k in t.iter_mut() {
+                    if *k == from {
+                        *k = to;
+                    }
+                }
+                self.tris[f] = Some(t);
+                self.faces[to].push(f);
+            }
+        }
+        self.faces[from].clear();
+        self.alive[from] = false;
+
+        // Costs change for every vertex whose neighborhood changed
+        let mut touched = self.neighbors(to);
+        touched.push(to);
+        for &k in touched.iter() {
+            self.version[k] += 1;
+        }
+        for &k in touched.iter() {
+            self.push_around(k);
+        }
+    }
+}
+
+/// Simplifies the mesh by quadric error metrics until it has at most
+/// `target` triangles (or until no more collapses are allowed).
+///
+/// Vertices are merged onto their neighbors rather than moved, so the
+/// result keeps each surviving vertex's height, biome, and color, and
+/// coastlines and biome boundaries are only ever simplified along
+/// themselves.  Plates, climate, and hydrology are not carried over.
+pub fn decimate(mesh : &Mesh, target : usize) -> Mesh {
+    let n = mesh.verts.len();
+    let mut faces = vec![Vec::new(); n];
+    let mut quadrics = vec![Quadric([0.0; 10]); n];
+    for (f, t) in mesh.tris.iter().enumerate() {
+        let (a, b, c) = (mesh.verts[t[0]], mesh.verts[t[1]], mesh.verts[t[2]]);
+        let cross = (b - a).cross(c - a);
+        let area = cross.magnitude() / 2.0;
+        if area > 0.0 {
+            let q = Quadric::plane(cross.normalize(), a, area);
+            for j in 0..3 {
+                quadrics[t[j]].add(&q);
+            }
+        }
+        for j in 0..3 {
+            faces[t[j]].push(f);
+        }
+    }
+
+    let mut s = Simplifier {
+        mesh : mesh,
+        tris : mesh.tris.iter().map(|t| { Some([t[0], t[1], t[2]]) }).collect(),
+        faces : faces,
+        quadrics : quadrics,
+        class : mesh.heights.iter().zip(mesh.biomes.iter())
+            .map(|(h, b)| { (*h >= mesh.sea_level, *b) })
+            .collect(),
+        alive : vec![true; n],
+        version : vec![0; n],
+        heap : BinaryHeap::new(),
+    };
+    for i in 0..n {
+        for j in s.neighbors(i) {
+            s.push(i, j);
+        }
+    }
+
+    let mut count = mesh.tris.len();
+    while count > target {
+        let c = match s.heap.pop() {
+            Some(c) => c,
+            None => break,
+        };
+        if !s.alive[c.from] || !s.alive[c.to] ||
+           c.stamp != (s.version[c.from], s.version[c.to]) ||
+           !s.allowed(c.from, c.to)
+        {
+            continue;
+        }
+        s.collapse(c.from, c.to);
+        count -= 2;
+    }
+
+    // Compact the surviving vertices and triangles
+    let mut index = vec![0; n];
+    let mut out = Mesh {
+        verts : Vec::new(), tris : Vec::new(), heights : Vec::new(),
+        sea_level : mesh.sea_level, temperature : Vec::new(), moisture : Vec::new(),
+        biomes : Vec::new(), colors : Vec::new(),
+        tectonics : None, climate : None, hydrology : None,
+    };
+    for i in (0..n).filter(|i| { s.alive[*i] }) {
+        index[i] = out.verts.len();
+        out.verts.push(mesh.verts[i]);
+        out.heights.push(mesh.heights[i]);
+        out.temperature.push(mesh.temperature[i]);
+        out.moisture.push(mesh.moisture[i]);
+        out.biomes.push(mesh.biomes[i]);
+        out.colors.push(mesh.colors[i]);
+    }
+    for t in s.tris.iter().filter_map(|t| { *t }) {
+        out.tris.push(Vector3::new(index[t[0]], index[t[1]], index[t[2]]));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use config::Config;
+
+    fn simplified() -> (Mesh, Mesh) {
+        let mesh = Mesh::generate(&Config { level : 4, .. Config::default() });
+        let low = decimate(&mesh, 2000);
+        (mesh, low)
+    }
+
+    #[test]
+    fn result_is_closed_and_manifold() {
+        let (_, low) = simplified();
+        let mut edges : HashMap<(usize, usize), usize> = HashMap::new();
+        for t in low.tris.iter() {
+            assert!(t[0] != t[1] && t[1] != t[2] && t[2] != t[0]);
+            for j in 0..3 {
+                *edges.entry((t[j], t[(j + 1) % 3])).or_insert(0) += 1;
+            }
+        }
+        for (&(a, b), &n) in edges.iter() {
+            assert_eq!(n, 1);
+            assert_eq!(edges.get(&(b, a)), Some(&1));
+        }
+        // Euler characteristic of a sphere
+        assert_eq!(low.verts.len() + low.tris.len() - edges.len() / 2, 2);
+    }
+
+    #[test]
+    fn result_meets_target() {
+        let (mesh, low) = simplified();
+        assert!(mesh.tris.len() > 2000);
+        assert!(low.tris.len() <= 2000);
+    }
+
+    #[test]
+    fn vertices_keep_their_class() {
+        let (mesh, low) = simplified();
+        for (i, v) in low.verts.iter().enumerate() {
+            let j = mesh.verts.iter().position(|w| { w == v }).unwrap();
+            assert_eq!(low.biomes[i], mesh.biomes[j]);
+            assert_eq!(low.heights[i] >= low.sea_level, mesh.heights[j] >= mesh.sea_level);
+            assert_eq!(low.colors[i], mesh.colors[j]);
+        }
+    }
+}
@@ -0,0 +1,170 @@
+extern crate cgmath;
+extern crate noise;
+
+use draw::util::Interpolator;
+use world::heightmap::{Heightmap, HeightmapParams};
+use world::noise_graph::NoiseGraph;
+use world::sphere::{latitude, longitude};
+
+use self::cgmath::Vector3;
+use self::noise::{NoiseFn, Seedable, MultiFractal};
+use self::noise::{Perlin, Fbm, RidgedMulti};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Parameters for the terrain height function
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Elevation {
+    pub seed : u32,
+
+    /// Replaces the built-in height function below, if present.
+    /// The graph's output is scaled by `amplitude`.
+    pub graph : Option<NoiseGraph>,
+
+    /// Image-based heights, blended with the noise if present
+    pub heightmap : Option<HeightmapParams>,
+
+    /// Maximum displacement from the unit sphere
+    pub amplitude : f64,
+
+    /// Base frequency of the fBm detail noise
+    pub frequency : f64,
+    pub octaves : usize,
+    pub persistence : f64,
+    pub lacunarity : f64,
+
+    /// Frequency of the low-frequency continent mask
+    pub continent_frequency : f64,
+    /// Blend between detail noise (0) and the continent mask (1)
+    pub continent_weight : f64,
+
+    /// Height of ridged mountain ranges, which only rise on land
+    pub mountains : f64,
+    pub mountain_frequency : f64,
+
+    /// Domain warping, which bends coastlines and ranges into swirls
+    pub warp_frequency : f64,
+    pub warp_strength : f64,
+}
+
+impl Default for Elevation {
+    fn default() -> Elevation {
+        Elevation {
+            seed : 0,
+            graph : None,
+            heightmap : None,
+            amplitude : 0.125,
+            frequency : 3.0,
+            octaves : 5,
+            persistence : 0.5,
+            lacunarity : 2.0,
+            continent_frequency : 1.0,
+            continent_weight : 0.6,
+            mountains : 0.5,
+            mountain_frequency : 4.0,
+            warp_frequency : 1.5,
+            warp_strength : 0.2,
+        }
+    }
+}
+
+/// A height function on the unit sphere, built from an `Elevation`.
+///
+/// `get` returns the radial displacement at a point on the unit sphere,
+/// so the terrain surface sits at `p * (1 + get(p))`.
+pub struct HeightField {
+    params : Elevation,
+    graph : Option<Box<NoiseFn<[f64; 3]>>>,
+    heightmap : Option<Heightmap>,
+    detail : Fbm,
+    continents : Fbm,
+    mountains : RidgedMulti,
+    warp : [Perlin; 3],
+
+    /// Flattens lowlands into plains while keeping peaks sharp
+    curve : Interpolator,
+}
+
+impl HeightField {
+    pub fn new(params : &Elevation) -> HeightField {
+        let seed = params.seed;
+        HeightField {
+            params : params.clone(),
+            graph : params.graph.as_ref().and_then(|g| {
+                g.compile().map_err(|err| {
+                    println!("Couldn't construct elevation graph: {}", err); }).ok() }),
+            heightmap : params.heightmap.as_ref().and_then(|h| {
+                Heightmap::load(h).map_err(|err| {
+                    println!("Couldn't load heightmap {}: {}", h.path, err); }).ok() }),
+            detail : Fbm::new()
+                .set_seed(seed)
+                .set_octaves(params.octaves)
+                .set_frequency(params.frequency)
+                .set_persistence(params.persistence)
+                .set_lacunarity(params.lacunarity),
+            continents : Fbm::new()
+                .set_seed(seed.wrapping_add(100))
+                .set_octaves(3)
+                .set_frequency(params.continent_frequency),
+            mountains : RidgedMulti::new()
+                .set_seed(seed.wrapping_add(200))
+                .set_octaves(params.octaves)
+                .set_frequency(params.mountain_frequency),
+            warp : [Perlin::new().set_seed(seed.wrapping_add(300)),
+                    Perlin::new().set_seed(seed.wrapping_add(301)),
+                    Perlin::new().set_seed(seed.wrapping_add(302))],
+            curve : Interpolator { pts : vec![
+                [-4.0, -4.0],
+                [ 0.0,  0.0],
+                [ 0.6,  0.2],
+                [ 1.0,  1.0],
+                [ 4.0,  4.0]] },
+        }
+    }
+}
+
+impl HeightField {
+    /// Height from the noise graph or the built-in height function
+    fn noise(&self, p : [f64; 3]) -> f64 {
+        let params = &self.params;
+        if let Some(ref g) = self.graph {
+            return g.get(p) * params.amplitude;
+        }
+
+        // Warp the lookup position with a low-frequency vector field
+        let w = [p[0] * params.warp_frequency,
+                 p[1] * params.warp_frequency,
+                 p[2] * params.warp_frequency];
+        let q = [p[0] + params.warp_strength * self.warp[0].get(w),
+                 p[1] + params.warp_strength * self.warp[1].get(w),
+                 p[2] + params.warp_strength * self.warp[2].get(w)];
+
+        let continent = self.continents.get(q);
+        let detail = self.detail.get(q);
+        let mut h = params.continent_weight * continent +
+                    (1.0 - params.continent_weight) * detail;
+
+        // Ridges are faded in from the coast towards continent interiors
+        let mask = (continent * 4.0).max(0.0).min(1.0);
+        let ridge = (self.mountains.get(q) + 1.0) / 2.0;
+        h += params.mountains * mask * mask * ridge;
+
+        let h = h.max(-4.0).min(4.0);
+        self.curve.at(h as f32) as f64 * params.amplitude
+    }
+}
+
+impl NoiseFn<[f64; 3]> for HeightField {
+    fn get(&self, p : [f64; 3]) -> f64 {
+        let noise = self.noise(p);
+        match (self.heightmap.as_ref(), self.params.heightmap.as_ref()) {
+            (Some(map), Some(h)) => {
+                let v = Vector3::new(p[0], p[1], p[2]);
+                let image = map.height(latitude(v), longitude(v));
+                noise * (1.0 - h.blend) + image * h.blend
+            },
+            _ => noise,
+        }
+    }
+}
@@ -0,0 +1,156 @@
+extern crate cgmath;
+extern crate rand;
+
+use std::cmp::Ordering;
+
+use world::mesh::Mesh;
+
+use self::cgmath::InnerSpace;
+use self::rand::distributions::{Range, Sample};
+use self::rand::{SeedableRng, ChaChaRng};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Parameters for the erosion pass
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Erosion {
+    pub seed : u32,
+
+    /// Number of water droplets to simulate
+    pub droplets : usize,
+    /// Maximum number of vertices that a droplet can visit
+    pub max_steps : usize,
+    /// Sediment carried per unit of slope (height drop over edge
+    /// length) and water
+    pub capacity : f64,
+    /// Fraction of spare capacity picked up at each step
+    pub erosion_rate : f64,
+    /// Fraction of excess sediment dropped at each step
+    pub deposition_rate : f64,
+    /// Fraction of a droplet's water lost at each step
+    pub evaporation : f64,
+
+    /// Number of thermal erosion passes over the whole mesh
+    pub thermal_iterations : usize,
+    /// Steepest stable slope (rise over run) before material slides
+    pub talus : f64,
+    /// Fraction of the excess height moved at each pass
+    pub thermal_rate : f64,
+}
+
+impl Default for Erosion {
+    fn default() -> Erosion {
+        Erosion {
+            seed : 0,
+            droplets : 20000,
+            max_steps : 40,
+            capacity : 0.02,
+            erosion_rate : 0.3,
+            deposition_rate : 0.3,
+            evaporation : 0.05,
+            thermal_iterations : 20,
+            talus : 0.8,
+            thermal_rate : 0.25,
+        }
+    }
+}
+
+/// Erodes the mesh's heights in place.
+///
+/// This runs on the vertex graph of the undisplaced sphere, so it must
+/// be called before vertices are moved out to their final heights.
+pub fn erode(mesh : &mut Mesh, params : &Erosion) {
+    let neighbors = mesh.neighbors();
+    hydraulic(mesh, &neighbors, params);
+    thermal(mesh, &neighbors, params);
+}
+
+/// Droplet-based hydraulic erosion: each droplet runs down the steepest
+/// edge, picking up sediment on steep slopes and dropping it in flats,
+/// pits, and the ocean.  Sediment is never lost: whatever a droplet
+/// still carries when it stops is left where it stopped.
+fn hydraulic(mesh : &mut Mesh, neighbors : &[Vec<usize>], params : &Erosion) {
+    let seed : &[_] = &[params.seed];
+    let mut rng : ChaChaRng = SeedableRng::from_seed(seed);
+    let mut start = Range::new(0, mesh.verts.len());
+
+    let sea = mesh.sea_level;
+    let verts = &mesh.verts;
+    let h = &mut mesh.heights;
+    for _ in 0..params.droplets {
+        let mut i = start.sample(&mut rng);
+        let mut water = 1.0;
+        let mut sediment : f64 = 0.0;
+
+        for _ in 0..params.max_steps {
+            if h[i] < sea {
+                break;
+            }
+
+            let next = neighbors[i].iter().cloned()
+                .min_by(|a, b| { h[*a].partial_cmp(&h[*b]).unwrap_or(Ordering::Equal) })
+                .unwrap();
+
+            // In a pit, fill it up to the lowest neighbor and stop
+            if h[next] >= h[i] {
+                let fill = sediment.min(h[next] - h[i] + 1e-6);
+                h[i] += fill;
+                sediment -= fill;
+                break;
+            }
+
+            let drop = h[i] - h[next];
+            let run = (verts[next] - verts[i]).magnitude();
+            let capacity = drop / run * water * params.capacity;
+            if sediment > capacity {
+                let d = (sediment - capacity) * params.deposition_rate;
+                h[i] += d;
+                sediment -= d;
+            } else {
+                // Never dig below the next vertex, which would make a pit
+                let e = ((capacity - sediment) * params.erosion_rate).min(drop);
+                h[i] -= e;
+                sediment += e;
+            }
+
+            water *= 1.0 - params.evaporation;
+            i = next;
+        }
+        deposit(h, neighbors, i, sediment);
+    }
+}
+
+/// Spreads leftover sediment evenly over a vertex and its neighbors,
+/// so that it doesn't pile up into a single spike
+fn deposit(h : &mut [f64], neighbors : &[Vec<usize>], i : usize, sediment : f64) {
+    let share = sediment / (neighbors[i].len() + 1) as f64;
+    h[i] += share;
+    for &j in &neighbors[i] {
+        h[j] += share;
+    }
+}
+
+/// Thermal erosion: material on slopes steeper than the talus angle
+/// slides down onto its lower neighbors.
+fn thermal(mesh : &mut Mesh, neighbors : &[Vec<usize>], params : &Erosion) {
+    let n = mesh.verts.len();
+    for _ in 0..params.thermal_iterations {
+        let mut delta = vec![0.0; n];
+        for i in 0..n {
+            for &j in &neighbors[i] {
+                let run = (mesh.verts[j] - mesh.verts[i]).magnitude();
+                let excess = (mesh.heights[i] - mesh.heights[j]) - params.talus * run;
+                if excess > 0.0 {
+                    // Split across neighbors so a peak can't be overdrawn
+                    let m = excess * params.thermal_rate / neighbors[i].len() as f64;
+                    delta[i] -= m;
+                    delta[j] += m;
+                }
+            }
+        }
+        for (h, d) in mesh.heights.iter_mut().zip(delta.iter()) {
+            *h += *d;
+        }
+    }
+}
@@ -0,0 +1,121 @@
+extern crate cgmath;
+extern crate image;
+extern crate noise;
+
+use std::error::Error;
+
+use world::noise_graph::{NoiseGraph, Fractal};
+use world::sphere::{equirect, latitude, from_lat_lon};
+
+use self::cgmath::{Vector3, InnerSpace};
+use self::image::{ImageBuffer, RgbImage};
+use self::noise::NoiseFn;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// A large vortex in the cloud bands
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Storm {
+    /// Position, in degrees
+    pub latitude : f64,
+    pub longitude : f64,
+    /// Radius, in radians
+    pub radius : f64,
+    pub color : [f32; 3],
+    /// Rotation at the storm's center, in radians; negative values
+    /// spin the other way
+    #[serde(default)]
+    pub twist : f64,
+}
+
+/// Parameters for a gas giant with no solid surface
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GasGiant {
+    /// Number of bands from pole to pole
+    pub bands : f64,
+    /// Band colors, cycled from north to south
+    pub palette : Vec<[f32; 3]>,
+
+    /// Noise that warps band latitude, giving turbulent edges
+    pub turbulence : NoiseGraph,
+    pub turbulence_strength : f64,
+
+    pub storms : Vec<Storm>,
+
+    /// Exponent for limb darkening; zero disables it
+    pub limb_darkening : f32,
+    /// How fast bands slip past each other, in texture widths per frame
+    pub shear : f32,
+}
+
+impl Default for GasGiant {
+    fn default() -> GasGiant {
+        GasGiant {
+            bands : 14.0,
+            palette : vec![[0.85, 0.75, 0.6], [0.7, 0.5, 0.35],
+                           [0.9, 0.85, 0.75], [0.6, 0.4, 0.3]],
+            turbulence : NoiseGraph::Fbm(Fractal {
+                seed : 500, octaves : 5, frequency : 4.0, .. Default::default() }),
+            turbulence_strength : 0.05,
+            storms : vec![Storm { latitude : -22.0, longitude : 40.0, radius : 0.15,
+                                  color : [0.75, 0.35, 0.2], twist : 3.0 }],
+            limb_darkening : 0.6,
+            shear : 0.0002,
+        }
+    }
+}
+
+fn lerp(a : [f32; 3], b : [f32; 3], t : f32) -> [f32; 3] {
+    [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t, a[2] + (b[2] - a[2]) * t]
+}
+
+/// Rotates `p` about the unit axis `k` by `angle` (Rodrigues' formula)
+fn rotate(p : Vector3<f64>, k : Vector3<f64>, angle : f64) -> Vector3<f64> {
+    p * angle.cos() + k.cross(p) * angle.sin() + k * k.dot(p) * (1.0 - angle.cos())
+}
+
+impl GasGiant {
+    /// Returns the cloud color at a point on the unit sphere
+    pub fn color(&self, p : Vector3<f64>, turbulence : &NoiseFn<[f64; 3]>) -> [f32; 3] {
+        // Swirl the lookup point around each storm's center
+        let mut q = p;
+        let mut storm = None;
+        for s in self.storms.iter() {
+            let c = from_lat_lon(s.latitude.to_radians(), s.longitude.to_radians());
+            let d = q.dot(c).min(1.0).acos() / s.radius;
+            if d < 1.0 {
+                q = rotate(q, c, s.twist * (1.0 - d) * (1.0 - d));
+                storm = Some((s, d));
+            }
+        }
+
+        let lat = latitude(q) + turbulence.get([q.x, q.y, q.z]) * self.turbulence_strength;
+        let v = (0.5 - lat / ::std::f64::consts::PI) * self.bands;
+        let n = self.palette.len();
+        if n == 0 {
+            return [0.5, 0.5, 0.5];
+        }
+        let i = v.floor().max(0.0) as usize;
+        let f = (v - v.floor()) as f32;
+        let mut color = lerp(self.palette[i % n], self.palette[(i + 1) % n],
+                             f * f * (3.0 - 2.0 * f));
+
+        if let Some((s, d)) = storm {
+            let t = (1.0 - d as f32).powi(2).min(1.0);
+            color = lerp(color, s.color, t);
+        }
+        color
+    }
+
+    /// Renders the cloud bands as an equirectangular texture
+    pub fn texture(&self, width : u32, height : u32) -> Result<RgbImage, Box<Error>> {
+        let turbulence = self.turbulence.compile()?;
+        Ok(ImageBuffer::from_fn(width, height, |x, y| {
+            let c = self.color(equirect(x, y, width, height), &*turbulence);
+            image::Rgb([(c[0].max(0.0).min(1.0) * 255.0) as u8,
+                        (c[1].max(0.0).min(1.0) * 255.0) as u8,
+                        (c[2].max(0.0).min(1.0) * 255.0) as u8])
+        }))
+    }
+}
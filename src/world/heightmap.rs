@@ -0,0 +1,192 @@
+extern crate image;
+extern crate png;
+extern crate serde_json;
+
+use std::error::Error;
+use std::f64::consts::PI;
+use std::fs::File;
+use std::path::Path;
+
+use self::png::HasParameters;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Parameters for driving terrain from an equirectangular image
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HeightmapParams {
+    /// Grayscale image, with north at the top and longitude -180 at
+    /// the left edge.  16-bit PNGs keep their full precision.
+    pub path : String,
+    /// Height of a white pixel above a black one, in planet radii
+    pub scale : f64,
+    /// Gray level (from 0 to 1) that maps to zero height
+    pub sea_level : f64,
+    /// Height range written by `maps` (as `PREFIXheight.json`); if
+    /// present, black and white map to its ends, in place of `scale`
+    /// and `sea_level`
+    pub range : Option<String>,
+    /// Blend between the noise height field (0) and the image (1)
+    pub blend : f64,
+}
+
+impl Default for HeightmapParams {
+    fn default() -> HeightmapParams {
+        HeightmapParams {
+            path : "heightmap.png".to_string(),
+            scale : 0.125,
+            sea_level : 0.5,
+            range : None,
+            blend : 1.0,
+        }
+    }
+}
+
+/// Lowest and highest heights in a map, in planet radii above sea level
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HeightRange {
+    pub min : f64,
+    pub max : f64,
+}
+
+/// Gray levels from an image, in the range [0, 1], and the heights
+/// that black and white stand for
+pub struct Heightmap {
+    width : usize,
+    height : usize,
+    data : Vec<f64>,
+    black : f64,
+    white : f64,
+}
+
+/// Reads a 16-bit grayscale PNG, which the image crate would cut down
+/// to 8 bits, or returns `None` if the file is anything else
+fn load_gray16(path : &Path) -> Option<(u32, u32, Vec<f64>)> {
+    let mut decoder = png::Decoder::new(File::open(path).ok()?);
+    decoder.set(png::Transformations::IDENTITY);
+    let (info, mut reader) = decoder.read_info().ok()?;
+    if info.color_type != png::ColorType::Grayscale ||
+       info.bit_depth != png::BitDepth::Sixteen
+    {
+        return None;
+    }
+    let mut buf = vec![0; info.buffer_size()];
+    reader.next_frame(&mut buf).ok()?;
+
+    // Samples are big-endian
+    let data = buf.chunks(2)
+        .map(|b| { ((b[0] as u32) << 8 | b[1] as u32) as f64 / 65535.0 })
+        .collect();
+    Some((info.width, info.height, data))
+}
+
+impl Heightmap {
+    /// Loads the image and works out the heights of its gray levels
+    pub fn load(params : &HeightmapParams) -> Result<Heightmap, Box<Error>> {
+        let path = Path::new(&params.path);
+        let (w, h, data) = match load_gray16(path) {
+            Some(d) => d,
+            None => {
+                let img = image::open(path)?.to_luma();
+                let (w, h) = img.dimensions();
+                (w, h, img.pixels().map(|p| { p.data[0] as f64 / 255.0 }).collect())
+            },
+        };
+        if w == 0 || h == 0 {
+            return Err(From::from("Heightmap is empty"));
+        }
+
+        let (black, white) = match params.range {
+            Some(ref r) => {
+                let r : HeightRange = serde_json::from_reader(File::open(r)?)?;
+                (r.min, r.max)
+            },
+            None => (-params.sea_level * params.scale,
+                     (1.0 - params.sea_level) * params.scale),
+        };
+        Ok(Heightmap { width : w as usize, height : h as usize, data : data,
+                       black : black, white : white })
+    }
+
+    /// Height above sea level at a latitude and longitude, in radians
+    pub fn height(&self, lat : f64, lon : f64) -> f64 {
+        self.black + (self.white - self.black) * self.sample(lat, lon)
+    }
+
+    /// Bilinearly samples the image at a latitude and longitude (in
+    /// radians), wrapping around in longitude and clamping at the poles
+    pub fn sample(&self, lat : f64, lon : f64) -> f64 {
+        // Pixel centers sit at half-integer coordinates
+        let u = (lon + PI) / (2.0 * PI) * self.width as f64 - 0.5;
+        let v = (PI / 2.0 - lat) / PI * self.height as f64 - 0.5;
+        let (fu, fv) = (u - u.floor(), v - v.floor());
+
+        let w = self.width as i64;
+        let x0 = (u.floor() as i64).rem_euclid(w) as usize;
+        let x1 = (x0 + 1) % self.width;
+        let row = |y : f64| { (y.max(0.0) as usize).min(self.height - 1) };
+        let (y0, y1) = (row(v.floor()), row(v.floor() + 1.0));
+
+        let at = |x : usize, y : usize| { self.data[y * self.width + x] };
+        let top = at(x0, y0) * (1.0 - fu) + at(x1, y0) * fu;
+        let bottom = at(x0, y1) * (1.0 - fu) + at(x1, y1) * fu;
+        top * (1.0 - fv) + bottom * fv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::io::Write;
+
+    /// A 4x2 map whose columns are 0, 0.25, 0.5, 1 on top, and all
+    /// 0.5 on the bottom row
+    fn map() -> Heightmap {
+        Heightmap { width : 4, height : 2,
+                    data : vec![0.0, 0.25, 0.5, 1.0, 0.5, 0.5, 0.5, 0.5],
+                    black : 0.0, white : 1.0 }
+    }
+
+    #[test]
+    fn sample_wraps_in_longitude() {
+        // Longitude +/-180 falls between the last and first columns
+        let m = map();
+        let lat = PI / 4.0;
+        assert!((m.sample(lat, -PI) - 0.5).abs() < 1e-9);
+        assert!((m.sample(lat, PI) - 0.5).abs() < 1e-9);
+        assert!((m.sample(lat, -PI + 1e-9) - m.sample(lat, PI - 1e-9)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn sample_clamps_at_poles() {
+        let m = map();
+        let lon = -PI + PI / 4.0;
+        assert!((m.sample(PI / 2.0, lon) - 0.0).abs() < 1e-9);
+        assert!((m.sample(-PI / 2.0, lon) - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn load_keeps_16_bits_and_range() {
+        let dir = env::temp_dir();
+        let png_path = dir.join("tiny-planets-heightmap-test.png");
+        let json_path = dir.join("tiny-planets-heightmap-test.json");
+
+        // 1000 / 65535 isn't a multiple of 1 / 255, so 8 bits would round it
+        let samples : [u16; 2] = [1000, 65535];
+        let bytes : Vec<u8> = samples.iter()
+            .flat_map(|s| { vec![(s >> 8) as u8, *s as u8] })
+            .collect();
+        image::save_buffer(&png_path, &bytes, 2, 1, image::ColorType::Gray(16)).unwrap();
+        File::create(&json_path).unwrap()
+            .write_all(br#"{"min": -0.1, "max": 0.3}"#).unwrap();
+
+        let params = HeightmapParams {
+            path : png_path.to_string_lossy().into_owned(),
+            range : Some(json_path.to_string_lossy().into_owned()),
+            .. HeightmapParams::default() };
+        let m = Heightmap::load(&params).unwrap();
+        assert!((m.data[0] - 1000.0 / 65535.0).abs() < 1e-9);
+        assert!((m.black + 0.1).abs() < 1e-9 && (m.white - 0.3).abs() < 1e-9);
+    }
+}
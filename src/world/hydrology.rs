@@ -0,0 +1,200 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use world::mesh::Mesh;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Parameters for drainage, lakes, and rivers
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HydrologyParams {
+    /// Fraction of the planet's total rainfall that must drain through
+    /// a vertex for it to become a river
+    pub threshold : f64,
+    /// Depth that rivers cut into the terrain at the threshold flow;
+    /// larger rivers cut deeper, logarithmically
+    pub carve_depth : f64,
+}
+
+impl Default for HydrologyParams {
+    fn default() -> HydrologyParams {
+        HydrologyParams {
+            threshold : 0.002,
+            carve_depth : 0.002,
+        }
+    }
+}
+
+/// Drainage results for each vertex of the terrain mesh
+pub struct Hydrology {
+    /// Water surface height, for vertices that are under a lake
+    pub lakes : Vec<Option<f64>>,
+    /// Fraction of the planet's rainfall draining through each vertex
+    pub flow : Vec<f64>,
+    /// Vertex that each vertex drains into; `None` for the ocean
+    pub receivers : Vec<Option<usize>>,
+    /// River segments, as (upstream, downstream) vertex pairs
+    pub rivers : Vec<(usize, usize)>,
+}
+
+/// Min-heap entry for the priority flood
+#[derive(PartialEq)]
+struct Cell {
+    level : f64,
+    index : usize,
+}
+
+impl Eq for Cell {}
+
+impl Ord for Cell {
+    fn cmp(&self, other : &Cell) -> Ordering {
+        other.level.partial_cmp(&self.level).unwrap_or(Ordering::Equal)
+            .then(other.index.cmp(&self.index))
+    }
+}
+
+impl PartialOrd for Cell {
+    fn partial_cmp(&self, other : &Cell) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Computes drainage on the mesh and carves rivers into its heights.
+///
+/// Like erosion, this must run before vertices are displaced.
+pub fn drain(mesh : &mut Mesh, params : &HydrologyParams) -> Hydrology {
+    let n = mesh.verts.len();
+    let neighbors = mesh.neighbors();
+    let sea = mesh.sea_level;
+    let h = &mut mesh.heights;
+
+    // Priority-flood from the ocean inwards: each vertex is reached from
+    // its lowest possible outlet, and any depression along the way is
+    // filled up to its spill height (forming a lake).
+    let mut filled = h.clone();
+    let mut receivers = vec![None; n];
+    let mut done = vec![false; n];
+    let mut order = Vec::with_capacity(n);
+    let mut heap = BinaryHeap::new();
+    for i in 0..n {
+        if h[i] < sea {
+            heap.push(Cell { level : h[i], index : i });
+            done[i] = true;
+        }
+    }
+    if heap.is_empty() {
+        // Without an ocean, everything drains to the lowest point
+        // (skipping NaN heights, so that the comparison is a total order)
+        let lowest = (0..n).filter(|i| { !h[*i].is_nan() })
+                           .min_by(|a, b| {
+                               h[*a].partial_cmp(&h[*b]).unwrap_or(Ordering::Equal) })
+                           .unwrap_or(0);
+        heap.push(Cell { level : h[lowest], index : lowest });
+        done[lowest] = true;
+    }
+    while let Some(Cell { level, index : i }) = heap.pop() {
+        order.push(i);
+        for &j in &neighbors[i] {
+            if !done[j] {
+                done[j] = true;
+                filled[j] = filled[j].max(level);
+                receivers[j] = Some(i);
+                heap.push(Cell { level : filled[j], index : j });
+            }
+        }
+    }
+
+    let lakes : Vec<Option<f64>> = (0..n)
+        .map(|i| { if filled[i] > h[i] { Some(filled[i]) } else { None } })
+        .collect();
+
+    // Accumulate rainfall from the headwaters downstream
+    let rain : Vec<f64> = match mesh.climate {
+        Some(ref c) => c.precipitation.clone(),
+        None => vec![1.0; n],
+    };
+    let total : f64 = rain.iter().zip(h.iter())
+        .filter(|&(_, h)| { *h >= sea })
+        .map(|(r, _)| { *r })
+        .sum();
+    let mut flow : Vec<f64> = rain.iter().zip(h.iter())
+        .map(|(r, h)| { if *h >= sea && total > 0.0 { r / total } else { 0.0 } })
+        .collect();
+    for &i in order.iter().rev() {
+        if let Some(r) = receivers[i] {
+            flow[r] += flow[i];
+        }
+    }
+
+    // Carve river beds, working upstream from the outlets so that each
+    // vertex is kept just above the (already carved) vertex below it.
+    let mut rivers = Vec::new();
+    for &i in order.iter() {
+        let r = match receivers[i] {
+            Some(r) => r,
+            None => continue,
+        };
+        if h[i] < sea || lakes[i].is_some() || flow[i] < params.threshold {
+            continue;
+        }
+        let floor = filled[r].max(sea) + 1e-6;
+        let carve = params.carve_depth * (1.0 + (flow[i] / params.threshold).ln());
+        h[i] = (h[i] - carve).max(floor.min(h[i]));
+        rivers.push((i, r));
+    }
+
+    Hydrology { lakes : lakes, flow : flow, receivers : receivers, rivers : rivers }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::{Config, SeaLevel};
+
+    #[test]
+    fn rivers_reach_the_sea_or_a_lake() {
+        let config = Config { level : 4, hydrology : Some(HydrologyParams::default()),
+                              .. Config::default() };
+        let mesh = Mesh::generate(&config);
+        let h = mesh.hydrology.as_ref().unwrap();
+        assert!(!h.rivers.is_empty());
+
+        let wet = |i : usize| { mesh.heights[i] < mesh.sea_level || h.lakes[i].is_some() };
+        let upstream : Vec<usize> = h.rivers.iter().map(|r| { r.0 }).collect();
+        for &(_, r) in h.rivers.iter() {
+            // Each river flows on into another river, or ends in water
+            assert!(wet(r) || upstream.contains(&r), "river ends dry at {}", r);
+
+            let mut i = r;
+            let mut steps = 0;
+            while !wet(i) {
+                i = h.receivers[i].expect("drainage stops on dry land");
+                steps += 1;
+                assert!(steps <= mesh.verts.len(), "drainage loops");
+            }
+        }
+    }
+
+    #[test]
+    fn drainage_matches_the_final_coastline() {
+        let config = Config { level : 4, sea_level : SeaLevel::LandFraction(0.4),
+                              hydrology : Some(HydrologyParams::default()),
+                              .. Config::default() };
+        let mesh = Mesh::generate(&config);
+        let h = mesh.hydrology.as_ref().unwrap();
+        for (i, r) in h.receivers.iter().enumerate() {
+            // Only the ocean drains nowhere
+            assert_eq!(r.is_none(), mesh.heights[i] < mesh.sea_level, "vertex {}", i);
+        }
+    }
+
+    #[test]
+    fn dry_planets_drain_to_the_lowest_finite_point() {
+        let mut mesh = Mesh::generate(&Config { level : 2, .. Config::default() });
+        mesh.sea_level = -10.0;
+        mesh.heights[0] = ::std::f64::NAN;
+        let h = drain(&mut mesh, &HydrologyParams::default());
+        assert_eq!(h.receivers.iter().filter(|r| { r.is_none() }).count(), 1);
+    }
+}
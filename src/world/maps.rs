@@ -0,0 +1,229 @@
+extern crate cgmath;
+extern crate image;
+extern crate noise;
+extern crate serde_json;
+
+use std::error::Error;
+use std::f64::consts::PI;
+use std::fs::File;
+
+use config::Config;
+use draw::icosphere::icosphere;
+use world::elevation::HeightField;
+use world::heightmap::HeightRange;
+use world::mesh::Mesh;
+use world::sphere::{equirect, latitude, Nearest};
+
+use self::cgmath::{Vector3, InnerSpace};
+use self::image::{ImageBuffer, GrayImage, RgbImage};
+use self::noise::NoiseFn;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Finds the mesh triangle under a point, for barycentric interpolation
+struct Locator<'a> {
+    mesh : &'a Mesh,
+    nearest : Nearest<'a>,
+    /// Triangles around each vertex
+    faces : Vec<Vec<usize>>,
+}
+
+impl<'a> Locator<'a> {
+    fn new(mesh : &'a Mesh) -> Locator<'a> {
+        let mut faces = vec![Vec::new(); mesh.verts.len()];
+        for (f, t) in mesh.tris.iter().enumerate() {
+            for j in 0..3 {
+                faces[t[j]].push(f);
+            }
+        }
+        Locator { mesh : mesh, nearest : Nearest::new(&mesh.verts), faces : faces }
+    }
+
+    /// Returns vertex indices and weights that sum to one.  The triangle
+    /// containing `p` is one of those around its nearest vertex; if none
+    /// match (which can happen near jittered vertices), the nearest
+    /// vertex is used on its own.
+    fn find(&self, p : Vector3<f64>) -> [(usize, f64); 3] {
+        let i = self.nearest.find(p);
+        let det = |a : Vector3<f64>, b : Vector3<f64>, c : Vector3<f64>| { a.dot(b.cross(c)) };
+        for &f in self.faces[i].iter() {
+            let t = self.mesh.tris[f];
+            let a = self.mesh.verts[t[0]].normalize();
+            let b = self.mesh.verts[t[1]].normalize();
+            let c = self.mesh.verts[t[2]].normalize();
+            let w = [det(p, b, c), det(a, p, c), det(a, b, p)];
+            let inside = w.iter().all(|w| { *w >= 0.0 }) || w.iter().all(|w| { *w <= 0.0 });
+            let sum = w[0] + w[1] + w[2];
+            if inside && sum != 0.0 {
+                return [(t[0], w[0] / sum), (t[1], w[1] / sum), (t[2], w[2] / sum)];
+            }
+        }
+        [(i, 1.0), (i, 0.0), (i, 0.0)]
+    }
+}
+
+/// Equirectangular maps of a planet's surface
+pub struct Maps {
+    pub width : u32,
+    pub height : u32,
+    /// Height above the mesh's sea level at each pixel, row by row
+    /// from the north
+    pub heights : Vec<f64>,
+    pub colors : Vec<[f32; 3]>,
+    /// Whether the planet has an ocean to fill in below sea level
+    pub wet : bool,
+}
+
+impl Maps {
+    /// Samples the planet at each pixel's center.
+    ///
+    /// Heights come from the same noise field as the terrain, evaluated
+    /// at full map resolution, plus whatever the mesh-level passes
+    /// (tectonics, craters, erosion, rivers) changed at nearby vertices.
+    /// Colors use the biome table without the per-vertex jitter.
+    pub fn new(config : &Config, mesh : &Mesh, width : u32) -> Maps {
+        let config = &config.seeded();
+        let height = width / 2;
+        let field = HeightField::new(&config.elevation);
+        let (dirs, _) = icosphere(config.level);
+        let residual : Vec<f64> = dirs.iter().zip(mesh.heights.iter())
+            .map(|(d, h)| { h - field.get([d.x, d.y, d.z]) })
+            .collect();
+
+        let locator = Locator::new(mesh);
+        let biomes = &config.biomes;
+        let moisture = biomes.moisture_field();
+
+        let mut heights = Vec::with_capacity((width * height) as usize);
+        let mut colors = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let p = equirect(x, y, width, height);
+                let w = locator.find(p);
+                let interp = |v : &[f64]| { w.iter().map(|&(i, k)| { v[i] * k }).sum::<f64>() };
+
+                let h = field.get([p.x, p.y, p.z]) + interp(&residual[..]) - mesh.sea_level;
+                let (t, m) = match mesh.climate {
+                    Some(ref c) => (interp(&c.temperature[..]), interp(&c.precipitation[..])),
+                    None => (biomes.temperature(latitude(p), h),
+                             (moisture.get([p.x, p.y, p.z]) + 1.0) / 2.0),
+                };
+                heights.push(h);
+                colors.push(biomes.table[biomes.classify(h, t, m)].color);
+            }
+        }
+        Maps { width : width, height : height, heights : heights, colors : colors,
+               wet : config.ocean.is_some() }
+    }
+
+    /// Returns the biome colors as an image, optionally with water
+    /// drawn in `ocean` (darkening with depth)
+    pub fn albedo(&self, ocean : Option<[f32; 3]>) -> RgbImage {
+        let w = self.width;
+        let byte = |c : f32| { (c.max(0.0).min(1.0) * 255.0).round() as u8 };
+        ImageBuffer::from_fn(w, self.height, |x, y| {
+            let i = (y * w + x) as usize;
+            let c = match ocean {
+                Some(o) if self.heights[i] < 0.0 => {
+                    let k = 1.0 / (1.0 - self.heights[i] as f32 * 10.0);
+                    [o[0] * k, o[1] * k, o[2] * k]
+                },
+                _ => self.colors[i],
+            };
+            image::Rgb([byte(c[0]), byte(c[1]), byte(c[2])])
+        })
+    }
+
+    /// Returns white for land and black for water, which is everything
+    /// below sea level unless the planet is dry
+    pub fn mask(&self) -> GrayImage {
+        let w = self.width;
+        ImageBuffer::from_fn(w, self.height, |x, y| {
+            let water = self.wet && self.heights[(y * w + x) as usize] < 0.0;
+            image::Luma([if water { 0 } else { 255u8 }])
+        })
+    }
+
+    /// Saves `{prefix}height.png` (16-bit grayscale), `{prefix}albedo.png`,
+    /// `{prefix}normal.png` (tangent space, with red east and green north),
+    /// and `{prefix}mask.png` (from `mask`).
+    ///
+    /// The height map spans the range in `{prefix}height.json`, in planet
+    /// radii above sea level.
+    pub fn save(&self, prefix : &str) -> Result<(), Box<Error>> {
+        let (w, h) = (self.width, self.height);
+        let at = |x : u32, y : u32| { self.heights[(y * w + x) as usize] };
+
+        let min = self.heights.iter().cloned().fold(::std::f64::INFINITY, f64::min);
+        let max = self.heights.iter().cloned().fold(::std::f64::NEG_INFINITY, f64::max);
+        let range = if max > min { max - min } else { 1.0 };
+
+        // 16-bit PNGs take big-endian samples
+        let mut bytes = Vec::with_capacity(self.heights.len() * 2);
+        for v in self.heights.iter() {
+            let s = ((v - min) / range * 65535.0).round() as u16;
+            bytes.push((s >> 8) as u8);
+            bytes.push(s as u8);
+        }
+        image::save_buffer(format!("{}height.png", prefix), &bytes, w, h,
+                           image::ColorType::Gray(16))?;
+        serde_json::to_writer_pretty(File::create(format!("{}height.json", prefix))?,
+                                     &HeightRange { min : min, max : max })?;
+
+        self.albedo(None).save(format!("{}albedo.png", prefix))?;
+
+        // Central differences, wrapping in longitude and clamping at the
+        // poles, with east-west spacing shrinking towards the poles
+        let step = PI / h as f64;
+        let normal = ImageBuffer::from_fn(w, h, |x, y| {
+            let lat = PI / 2.0 - (y as f64 + 0.5) * step;
+            let east = (at((x + 1) % w, y) - at((x + w - 1) % w, y))
+                     / (2.0 * step * lat.cos().max(1e-3));
+            let north = (at(x, y.saturating_sub(1)) - at(x, (y + 1).min(h - 1)))
+                      / (2.0 * step);
+            let n = Vector3::new(-east, -north, 1.0).normalize();
+            let c = |v : f64| { ((v * 0.5 + 0.5) * 255.0).round() as u8 };
+            image::Rgb([c(n.x), c(n.y), c(n.z)])
+        });
+        normal.save(format!("{}normal.png", prefix))?;
+
+        self.mask().save(format!("{}mask.png", prefix))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use self::image::GenericImage;
+
+    #[test]
+    fn saved_images_are_twice_as_wide_as_tall() {
+        let config = Config { level : 3, .. Config::default() };
+        let mesh = Mesh::generate(&config);
+        let maps = Maps::new(&config, &mesh, 32);
+        assert_eq!((maps.width, maps.height), (32, 16));
+        assert_eq!(maps.heights.len(), 32 * 16);
+
+        let prefix = env::temp_dir().join("tiny-planets-maps-test-");
+        let prefix = prefix.to_string_lossy();
+        maps.save(&prefix).unwrap();
+        for name in ["height", "albedo", "normal", "mask"].iter() {
+            let img = image::open(format!("{}{}.png", prefix, name)).unwrap();
+            assert_eq!(img.dimensions(), (32, 16), "{}", name);
+        }
+    }
+
+    #[test]
+    fn dry_planets_are_all_land() {
+        let config = Config { level : 3, ocean : None, .. Config::default() };
+        let mesh = Mesh::generate(&config);
+        let maps = Maps::new(&config, &mesh, 16);
+        assert!(maps.heights.iter().any(|h| { *h < 0.0 }));
+        assert!(maps.mask().pixels().all(|p| { p.data[0] == 255 }));
+
+        let wet = Maps::new(&Config { level : 3, .. Config::default() }, &mesh, 16);
+        assert!(wet.mask().pixels().any(|p| { p.data[0] == 0 }));
+    }
+}
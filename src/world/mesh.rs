@@ -0,0 +1,246 @@
+extern crate cgmath;
+extern crate noise;
+extern crate rand;
+
+use std::cmp::Ordering;
+
+use config::{Config, SeaLevel};
+use draw::icosphere::icosphere;
+use world::climate::Climate;
+use world::craters::stamp;
+use world::elevation::HeightField;
+use world::erosion::erode;
+use world::hydrology::{Hydrology, drain};
+use world::sphere::{latitude, triangle_area};
+use world::tectonics::Tectonics;
+
+use self::cgmath::{Vector3, InnerSpace};
+use self::rand::distributions::{Range, Sample};
+use self::rand::{SeedableRng, ChaChaRng};
+
+use self::noise::NoiseFn;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// CPU-side terrain geometry, shared by the renderer and other consumers
+pub struct Mesh {
+    /// Final vertex positions, including jitter
+    pub verts : Vec<Vector3<f64>>,
+    pub tris : Vec<Vector3<usize>>,
+
+    /// Height above the unit sphere at each vertex
+    pub heights : Vec<f64>,
+    /// Height of the ocean surface above the unit sphere
+    pub sea_level : f64,
+    /// Annual mean temperature at each vertex, on the biome table's scale
+    pub temperature : Vec<f64>,
+    /// Moisture at each vertex, from 0 to 1
+    pub moisture : Vec<f64>,
+    /// Index into the config's biome table at each vertex
+    pub biomes : Vec<usize>,
+    pub colors : Vec<[f32; 3]>,
+
+    /// Plate assignment, if enabled in the config
+    pub tectonics : Option<Tectonics>,
+    /// Simulated climate, if enabled in the config
+    pub climate : Option<Climate>,
+    /// Lakes and rivers, if enabled in the config
+    pub hydrology : Option<Hydrology>,
+}
+
+impl Mesh {
+    pub fn generate(config : &Config) -> Mesh {
+        let config = &config.seeded();
+        let (v, i) = icosphere(config.level);
+        let height = HeightField::new(&config.elevation);
+        let heights = v.iter().map(|p| { height.get([p.x, p.y, p.z]) }).collect();
+
+        let mut mesh = Mesh { verts : v, tris : i, heights : heights, sea_level : 0.0,
+                              temperature : Vec::new(), moisture : Vec::new(),
+                              biomes : Vec::new(), colors : Vec::new(),
+                              tectonics : None, climate : None, hydrology : None };
+        if let Some(ref p) = config.tectonics {
+            let t = Tectonics::generate(&mesh, p);
+            for (h, e) in mesh.heights.iter_mut().zip(t.elevation.iter()) {
+                *h = *h * (1.0 - p.blend) + e * p.blend;
+            }
+            mesh.tectonics = Some(t);
+        }
+        if let Some(ref c) = config.craters {
+            stamp(&mut mesh, c);
+        }
+        // Erosion lowers the terrain, so a land fraction is resolved
+        // again afterwards to keep the coastline honest
+        mesh.sea_level = mesh.find_sea_level(config.sea_level);
+        if let Some(ref e) = config.erosion {
+            erode(&mut mesh, e);
+            mesh.sea_level = mesh.find_sea_level(config.sea_level);
+        }
+        // Climate only needs heights and directions, so it runs on the
+        // undisplaced sphere, where later passes can read its rainfall
+        mesh.climate = config.climate.as_ref().map(|c| {
+            Climate::simulate(&mesh, c, &config.spin, config.biomes.lapse_rate) });
+        // Rivers are routed against this final sea level, and carving
+        // keeps land above it, so the land fraction still holds afterwards
+        if let Some(ref p) = config.hydrology {
+            mesh.hydrology = Some(drain(&mut mesh, p));
+        }
+
+        let seed: &[_] = &[0];
+        let mut rng : ChaChaRng = SeedableRng::from_seed(seed);
+        let mut between = Range::new(-0.01, 0.01);
+        let mut jitter = || { between.sample(&mut rng) };
+
+        for (v, h) in mesh.verts.iter_mut().zip(mesh.heights.iter()) {
+            // Scale based on the terrain height field
+            *v *= h + 1.0;
+
+            // Add a little random jitter
+            v.x += jitter();
+            v.y += jitter();
+            v.z += jitter();
+        }
+
+        mesh.classify(config, &mut jitter);
+        mesh
+    }
+
+    /// Resolves a sea level setting against the current heights
+    fn find_sea_level(&self, sea : SeaLevel) -> f64 {
+        match sea {
+            SeaLevel::Height(h) => h,
+            SeaLevel::LandFraction(f) => {
+                // Icosphere vertices are spread almost evenly over the
+                // sphere, so a height quantile is close to an area quantile
+                let mut sorted = self.heights.clone();
+                sorted.sort_by(|a, b| { a.partial_cmp(b).unwrap_or(Ordering::Equal) });
+                let f = f.max(0.0).min(1.0);
+                let i = ((1.0 - f) * sorted.len() as f64) as usize;
+                if i == 0 {
+                    sorted[0] - 1e-6
+                } else if i >= sorted.len() {
+                    sorted[sorted.len() - 1] + 1e-6
+                } else {
+                    (sorted[i - 1] + sorted[i]) / 2.0
+                }
+            },
+        }
+    }
+
+    /// Assigns a biome and color to every vertex
+    fn classify<R>(&mut self, config : &Config, jitter : &mut R)
+        where R : FnMut() -> f64
+    {
+        let biomes = &config.biomes;
+        let moisture = biomes.moisture_field();
+
+        self.temperature.clear();
+        self.moisture.clear();
+        self.biomes.clear();
+        self.colors.clear();
+        for (i, (pos, h)) in self.verts.iter().zip(self.heights.iter()).enumerate() {
+            let h = *h - self.sea_level;
+            let (t, m) = match self.climate {
+                Some(ref c) => (c.temperature[i], c.precipitation[i]),
+                None => (biomes.temperature(latitude(*pos), h),
+                         (moisture.get([pos.x, pos.y, pos.z]) + 1.0) / 2.0),
+            };
+            let b = biomes.classify(h, t, m);
+
+            // Jitter is drawn from [-0.01, 0.01], so rescale it
+            let mut color = biomes.table[b].color;
+            for c in color.iter_mut() {
+                *c += biomes.table[b].jitter * 100.0 * jitter() as f32;
+            }
+            self.temperature.push(t);
+            self.moisture.push(m);
+            self.biomes.push(b);
+            self.colors.push(color);
+        }
+    }
+
+    /// Returns area-weighted vertex normals.
+    ///
+    /// Normals follow the winding of the icosphere's triangles,
+    /// which point into the sphere.
+    pub fn normals(&self) -> Vec<Vector3<f64>> {
+        // The cross product's magnitude is twice the triangle's area,
+        // so we skip normalizing until every face has been added.
+        let mut normals = vec![Vector3::new(0.0, 0.0, 0.0); self.verts.len()];
+        for t in self.tris.iter() {
+            let a = self.verts[t[0]];
+            let b = self.verts[t[1]];
+            let c = self.verts[t[2]];
+
+            let norm = (b - a).cross(c - a);
+            for j in 0..3 {
+                normals[t[j]] += norm;
+            }
+        }
+        normals.iter().map(|n| { n.normalize() }).collect()
+    }
+
+    /// Returns the area of the unit sphere belonging to each vertex,
+    /// which is a third of each surrounding spherical triangle.
+    /// The areas sum to 4 pi.
+    pub fn areas(&self) -> Vec<f64> {
+        let mut out = vec![0.0; self.verts.len()];
+        for t in self.tris.iter() {
+            let a = triangle_area(self.verts[t[0]].normalize(),
+                                  self.verts[t[1]].normalize(),
+                                  self.verts[t[2]].normalize());
+            for j in 0..3 {
+                out[t[j]] += a / 3.0;
+            }
+        }
+        out
+    }
+
+    /// Returns the neighbors of each vertex in the triangle graph
+    pub fn neighbors(&self) -> Vec<Vec<usize>> {
+        let mut out = vec![Vec::new(); self.verts.len()];
+        for t in self.tris.iter() {
+            for j in 0..3 {
+                let (a, b) = (t[j], t[(j + 1) % 3]);
+                if !out[a].contains(&b) {
+                    out[a].push(b);
+                    out[b].push(a);
+                }
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use world::erosion::Erosion;
+    use world::hydrology::HydrologyParams;
+
+    fn land(mesh : &Mesh) -> f64 {
+        mesh.heights.iter().filter(|h| { **h >= mesh.sea_level }).count() as f64
+            / mesh.heights.len() as f64
+    }
+
+    #[test]
+    fn land_fraction_is_a_height_quantile() {
+        for &f in [0.0, 0.3, 0.5, 1.0].iter() {
+            let config = Config { level : 4, sea_level : SeaLevel::LandFraction(f),
+                                  .. Config::default() };
+            let mesh = Mesh::generate(&config);
+            assert!((land(&mesh) - f).abs() < 1e-2, "{} vs {}", land(&mesh), f);
+        }
+    }
+
+    #[test]
+    fn land_fraction_holds_after_erosion() {
+        let config = Config { level : 4, sea_level : SeaLevel::LandFraction(0.4),
+                              erosion : Some(Erosion { droplets : 2000,
+                                                       .. Erosion::default() }),
+                              hydrology : Some(HydrologyParams::default()),
+                              .. Config::default() };
+        let mesh = Mesh::generate(&config);
+        assert!((land(&mesh) - 0.4).abs() < 1e-2);
+    }
+}
@@ -0,0 +1,17 @@
+pub mod biome;
+pub mod climate;
+pub mod craters;
+pub mod decimate;
+pub mod elevation;
+pub mod erosion;
+pub mod gas_giant;
+pub mod heightmap;
+pub mod hydrology;
+pub mod maps;
+pub mod mesh;
+pub mod noise_graph;
+pub mod orbit;
+pub mod rings;
+pub mod sphere;
+pub mod stats;
+pub mod tectonics;
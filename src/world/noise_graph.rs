@@ -0,0 +1,363 @@
+extern crate noise;
+
+use std::error::Error;
+
+use draw::util::Interpolator;
+
+use self::noise::{NoiseFn, Seedable, MultiFractal};
+use self::noise::{Perlin, Billow, Fbm, RidgedMulti};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Parameters shared by the fractal noise sources
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Fractal {
+    pub seed : u32,
+    pub octaves : usize,
+    pub frequency : f64,
+    pub persistence : f64,
+    pub lacunarity : f64,
+}
+
+impl Default for Fractal {
+    fn default() -> Fractal {
+        Fractal {
+            seed : 0,
+            octaves : 6,
+            frequency : 1.0,
+            persistence : 0.5,
+            lacunarity : 2.0,
+        }
+    }
+}
+
+fn one() -> f64 { 1.0 }
+
+/// A serializable description of a noise function.
+///
+/// Graphs are written as nested JSON objects tagged by `type`, e.g.
+/// `{"type": "scale", "scale": [20, 20, 20], "source": {"type": "perlin"}}`,
+/// then turned into a callable `NoiseFn` with `compile`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NoiseGraph {
+    Perlin {
+        #[serde(default)]
+        seed : u32,
+    },
+    Fbm(Fractal),
+    Billow(Fractal),
+    Ridged(Fractal),
+    Constant {
+        value : f64,
+    },
+
+    /// Scales the input point before passing it to the source
+    Scale {
+        source : Box<NoiseGraph>,
+        scale : [f64; 3],
+    },
+    /// Applies `scale * x + bias` to the source's output
+    ScaleBias {
+        source : Box<NoiseGraph>,
+        #[serde(default = "one")]
+        scale : f64,
+        #[serde(default)]
+        bias : f64,
+    },
+    Abs {
+        source : Box<NoiseGraph>,
+    },
+    /// Remaps the output through a piecewise-linear curve, given as at
+    /// least one `[input, output]` pair in strictly increasing input order
+    Curve {
+        source : Box<NoiseGraph>,
+        points : Vec<[f64; 2]>,
+    },
+    Add {
+        sources : Vec<NoiseGraph>,
+    },
+    Multiply {
+        sources : Vec<NoiseGraph>,
+    },
+
+    /// Picks `b` where `control` is within `[lower, upper]` and `a`
+    /// elsewhere, smoothing the transition over `falloff`
+    Select {
+        a : Box<NoiseGraph>,
+        b : Box<NoiseGraph>,
+        control : Box<NoiseGraph>,
+        lower : f64,
+        upper : f64,
+        #[serde(default)]
+        falloff : f64,
+    },
+    /// Linear blend from `a` (control = -1) to `b` (control = 1)
+    Blend {
+        a : Box<NoiseGraph>,
+        b : Box<NoiseGraph>,
+        control : Box<NoiseGraph>,
+    },
+    /// Randomly displaces the input point before passing it to the source
+    Turbulence {
+        source : Box<NoiseGraph>,
+        #[serde(default)]
+        seed : u32,
+        #[serde(default = "one")]
+        frequency : f64,
+        #[serde(default = "one")]
+        power : f64,
+    },
+}
+
+impl NoiseGraph {
+    /// Builds a noise function that evaluates this graph, or returns an
+    /// error if any part of it is malformed
+    pub fn compile(&self) -> Result<Box<NoiseFn<[f64; 3]>>, Box<Error>> {
+        Ok(Box::new(self.node()?))
+    }
+
+    /// Shorthand for a source evaluated at a uniformly scaled point
+    pub fn scaled(source : NoiseGraph, scale : f64) -> NoiseGraph {
+        NoiseGraph::Scale { source : Box::new(source),
+                            scale : [scale, scale, scale] }
+    }
+
+    /// Adds `offset` to the seed of every noise source in the graph
+    pub fn reseed(&mut self, offset : u32) {
+        match *self {
+            NoiseGraph::Perlin { ref mut seed } => *seed = seed.wrapping_add(offset),
+            NoiseGraph::Fbm(ref mut f) | NoiseGraph::Billow(ref mut f) |
+            NoiseGraph::Ridged(ref mut f) => f.seed = f.seed.wrapping_add(offset),
+            NoiseGraph::Constant { .. } => (),
+            NoiseGraph::Scale { ref mut source, .. } |
+            NoiseGraph::ScaleBias { ref mut source, .. } |
+            NoiseGraph::Abs { ref mut source } |
+            NoiseGraph::Curve { ref mut source, .. } => source.reseed(offset),
+            NoiseGraph::Add { ref mut sources } |
+            NoiseGraph::Multiply { ref mut sources } =>
+                for s in sources.iter_mut() {
+                    s.reseed(offset);
+                },
+            NoiseGraph::Select { ref mut a, ref mut b, ref mut control, .. } |
+            NoiseGraph::Blend { ref mut a, ref mut b, ref mut control } => {
+                a.reseed(offset);
+                b.reseed(offset);
+                control.reseed(offset);
+            },
+            NoiseGraph::Turbulence { ref mut source, ref mut seed, .. } => {
+                *seed = seed.wrapping_add(offset);
+                source.reseed(offset);
+            },
+        }
+    }
+
+    fn node(&self) -> Result<Node, Box<Error>> {
+        let fractal = |f : &Fractal| { (f.seed, f.octaves, f.frequency,
+                                        f.persistence, f.lacunarity) };
+        let nodes = |ss : &[NoiseGraph]| -> Result<Vec<Node>, Box<Error>> {
+            ss.iter().map(|s| { s.node() }).collect()
+        };
+        Ok(match *self {
+            NoiseGraph::Perlin { seed } =>
+                Node::Source(Box::new(Perlin::new().set_seed(seed))),
+            NoiseGraph::Fbm(ref f) => {
+                let (s, o, fr, p, l) = fractal(f);
+                Node::Source(Box::new(Fbm::new()
+                    .set_seed(s).set_octaves(o).set_frequency(fr)
+                    .set_persistence(p).set_lacunarity(l)))
+            },
+            NoiseGraph::Billow(ref f) => {
+                let (s, o, fr, p, l) = fractal(f);
+                Node::Source(Box::new(Billow::new()
+                    .set_seed(s).set_octaves(o).set_frequency(fr)
+                    .set_persistence(p).set_lacunarity(l)))
+            },
+            NoiseGraph::Ridged(ref f) => {
+                let (s, o, fr, p, l) = fractal(f);
+                Node::Source(Box::new(RidgedMulti::new()
+                    .set_seed(s).set_octaves(o).set_frequency(fr)
+                    .set_persistence(p).set_lacunarity(l)))
+            },
+            NoiseGraph::Constant { value } => Node::Constant(value),
+            NoiseGraph::Scale { ref source, scale } =>
+                Node::Scale(Box::new(source.node()?), scale),
+            NoiseGraph::ScaleBias { ref source, scale, bias } =>
+                Node::ScaleBias(Box::new(source.node()?), scale, bias),
+            NoiseGraph::Abs { ref source } =>
+                Node::Abs(Box::new(source.node()?)),
+            NoiseGraph::Curve { ref source, ref points } => {
+                if points.is_empty() {
+                    return Err(From::from("Curve needs at least one point"));
+                }
+                if let Some(w) = points.windows(2).find(|w| { !(w[0][0] < w[1][0]) }) {
+                    return Err(From::from(format!(
+                        "Curve inputs must be strictly increasing, but {} is followed by {}",
+                        w[0][0], w[1][0])));
+                }
+                let pts = points.iter()
+                    .map(|p| { [p[0] as f32, p[1] as f32] })
+                    .collect();
+                Node::Curve(Box::new(source.node()?), Interpolator { pts : pts })
+            },
+            NoiseGraph::Add { ref sources } => Node::Add(nodes(sources)?),
+            NoiseGraph::Multiply { ref sources } => Node::Multiply(nodes(sources)?),
+            NoiseGraph::Select { ref a, ref b, ref control,
+                                 lower, upper, falloff } =>
+                Node::Select(Box::new(a.node()?), Box::new(b.node()?),
+                             Box::new(control.node()?), lower, upper, falloff),
+            NoiseGraph::Blend { ref a, ref b, ref control } =>
+                Node::Blend(Box::new(a.node()?), Box::new(b.node()?),
+                            Box::new(control.node()?)),
+            NoiseGraph::Turbulence { ref source, seed, frequency, power } =>
+                Node::Turbulence(Box::new(source.node()?),
+                                 [Perlin::new().set_seed(seed),
+                                  Perlin::new().set_seed(seed.wrapping_add(1)),
+                                  Perlin::new().set_seed(seed.wrapping_add(2))],
+                                 frequency, power),
+        })
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Compiled form of a `NoiseGraph`, with its noise sources constructed
+enum Node {
+    Source(Box<NoiseFn<[f64; 3]>>),
+    Constant(f64),
+    Scale(Box<Node>, [f64; 3]),
+    ScaleBias(Box<Node>, f64, f64),
+    Abs(Box<Node>),
+    Curve(Box<Node>, Interpolator),
+    Add(Vec<Node>),
+    Multiply(Vec<Node>),
+    Select(Box<Node>, Box<Node>, Box<Node>, f64, f64, f64),
+    Blend(Box<Node>, Box<Node>, Box<Node>),
+    Turbulence(Box<Node>, [Perlin; 3], f64, f64),
+}
+
+fn lerp(a : f64, b : f64, t : f64) -> f64 {
+    a + (b - a) * t
+}
+
+fn s_curve(t : f64) -> f64 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+impl NoiseFn<[f64; 3]> for Node {
+    fn get(&self, p : [f64; 3]) -> f64 {
+        match *self {
+            Node::Source(ref s) => s.get(p),
+            Node::Constant(v) => v,
+            Node::Scale(ref s, k) => s.get([p[0] * k[0], p[1] * k[1], p[2] * k[2]]),
+            Node::ScaleBias(ref s, scale, bias) => s.get(p) * scale + bias,
+            Node::Abs(ref s) => s.get(p).abs(),
+            Node::Curve(ref s, ref curve) => {
+                // Clamp to the curve's domain, since the interpolator
+                // returns zero for anything outside of it.  Compiling
+                // checked that there's at least one point.
+                let lo = curve.pts[0][0];
+                let hi = curve.pts[curve.pts.len() - 1][0];
+                curve.at((s.get(p) as f32).max(lo).min(hi)) as f64
+            },
+            Node::Add(ref ss) => ss.iter().map(|s| { s.get(p) }).sum(),
+            Node::Multiply(ref ss) => ss.iter().map(|s| { s.get(p) }).product(),
+            Node::Select(ref a, ref b, ref control, lower, upper, falloff) => {
+                let c = control.get(p);
+                if falloff > 0.0 {
+                    if c < lower - falloff || c > upper + falloff {
+                        a.get(p)
+                    } else if c < lower + falloff {
+                        let t = (c - lower + falloff) / (2.0 * falloff);
+                        lerp(a.get(p), b.get(p), s_curve(t))
+                    } else if c < upper - falloff {
+                        b.get(p)
+                    } else {
+                        let t = (c - upper + falloff) / (2.0 * falloff);
+                        lerp(b.get(p), a.get(p), s_curve(t))
+                    }
+                } else if c < lower || c > upper {
+                    a.get(p)
+                } else {
+                    b.get(p)
+                }
+            },
+            Node::Blend(ref a, ref b, ref control) => {
+                let t = (control.get(p) + 1.0) / 2.0;
+                lerp(a.get(p), b.get(p), t)
+            },
+            Node::Turbulence(ref s, ref warp, frequency, power) => {
+                let q = [p[0] * frequency, p[1] * frequency, p[2] * frequency];
+                s.get([p[0] + power * warp[0].get(q),
+                       p[1] + power * warp[1].get(q),
+                       p[2] + power * warp[2].get(q)])
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn curve(points : Vec<[f64; 2]>) -> NoiseGraph {
+        NoiseGraph::Curve { source : Box::new(NoiseGraph::Constant { value : 0.5 }),
+                            points : points }
+    }
+
+    #[test]
+    fn empty_curve_is_rejected() {
+        assert!(curve(vec![]).compile().is_err());
+    }
+
+    #[test]
+    fn unsorted_curve_is_rejected() {
+        assert!(curve(vec![[0.0, 0.0], [-1.0, 1.0]]).compile().is_err());
+        assert!(curve(vec![[0.0, 0.0], [0.0, 1.0]]).compile().is_err());
+    }
+
+    #[test]
+    fn nested_errors_are_reported() {
+        let g = NoiseGraph::Add { sources : vec![NoiseGraph::Perlin { seed : 0 },
+                                                 curve(vec![])] };
+        assert!(g.compile().is_err());
+    }
+
+    #[test]
+    fn reseed_reaches_nested_sources() {
+        let mut g = NoiseGraph::Add { sources : vec![
+            NoiseGraph::Perlin { seed : 1 },
+            NoiseGraph::scaled(NoiseGraph::Fbm(Fractal { seed : 2, .. Default::default() }), 2.0),
+        ] };
+        g.reseed(10);
+        match g {
+            NoiseGraph::Add { ref sources } => {
+                match sources[0] {
+                    NoiseGraph::Perlin { seed } => assert_eq!(seed, 11),
+                    _ => unreachable!(),
+                }
+                match sources[1] {
+                    NoiseGraph::Scale { ref source, .. } => match **source {
+                        NoiseGraph::Fbm(ref f) => assert_eq!(f.seed, 12),
+                        _ => unreachable!(),
+                    },
+                    _ => unreachable!(),
+                }
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn curves_interpolate_and_clamp() {
+        let f = curve(vec![[0.0, 0.0], [1.0, 2.0]]).compile().unwrap();
+        assert!((f.get([0.0, 0.0, 0.0]) - 1.0).abs() < 1e-6);
+
+        let f = curve(vec![[-1.0, 3.0]]).compile().unwrap();
+        assert!((f.get([0.0, 0.0, 0.0]) - 3.0).abs() < 1e-6);
+
+        let f = curve(vec![[0.75, -1.0], [1.0, 1.0]]).compile().unwrap();
+        assert!((f.get([0.0, 0.0, 0.0]) + 1.0).abs() < 1e-6);
+    }
+}
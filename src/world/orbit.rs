@@ -0,0 +1,134 @@
+extern crate cgmath;
+
+use std::f64::consts::PI;
+
+use self::cgmath::{Vector3, Matrix3, Deg, Rad, InnerSpace};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// A circular orbit around a parent body
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Orbit {
+    /// Distance from the parent's center, in parent radii
+    pub radius : f64,
+    /// Time for one orbit, in frames
+    pub period : f64,
+    /// Tilt of the orbital plane, in degrees
+    pub inclination : f64,
+    /// Starting angle along the orbit, in degrees
+    pub phase : f64,
+}
+
+impl Default for Orbit {
+    fn default() -> Orbit {
+        Orbit {
+            radius : 3.0,
+            period : 2000.0,
+            inclination : 5.0,
+            phase : 0.0,
+        }
+    }
+}
+
+impl Orbit {
+    /// Returns the offset from the parent's center at a given frame
+    pub fn position(&self, time : f64) -> Vector3<f64> {
+        let angle = self.phase.to_radians() + 2.0 * PI * time / self.period;
+        let flat = Vector3::new(angle.cos(), 0.0, angle.sin()) * self.radius;
+        Matrix3::from_angle_x(Deg(self.inclination)) * flat
+    }
+}
+
+/// Direction towards the star, in view space.  The camera sits in the
+/// orbital plane, and the scene is drawn in a frame that turns with the
+/// orbit, so the star stays put while each planet's axis swings around.
+pub fn sun() -> Vector3<f64> {
+    Vector3::new(0.5, 0.0, -1.0).normalize()
+}
+
+/// How a body spins, and where it is in its orbit around the star
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Spin {
+    /// Tilt of the rotation axis from the orbit's normal, in degrees
+    pub axial_tilt : f64,
+    /// Time for one rotation, in frames
+    pub day : f64,
+    /// Time for one orbit around the star, in frames
+    pub year : f64,
+    /// Starting angle along the orbit, in degrees; 0 is the northern
+    /// spring equinox and 90 the northern summer solstice
+    pub season : f64,
+
+    /// Temperature below which seasonal snow settles
+    pub snow_line : f64,
+    /// Temperature change at the poles when the sun is overhead at
+    /// 90 degrees of declination; scaled by the actual declination
+    pub seasonal_swing : f64,
+}
+
+impl Default for Spin {
+    fn default() -> Spin {
+        Spin {
+            axial_tilt : 23.4,
+            day : 200.0 * PI,
+            year : 20000.0,
+            season : 0.0,
+            snow_line : 0.15,
+            seasonal_swing : 1.5,
+        }
+    }
+}
+
+impl Spin {
+    /// Angle along the orbit at a given frame, in radians
+    fn orbit_angle(&self, time : f64) -> f64 {
+        self.season.to_radians() + 2.0 * PI * time / self.year
+    }
+
+    /// Latitude at which the sun is overhead, in radians
+    pub fn declination(&self, time : f64) -> f64 {
+        (self.axial_tilt.to_radians().sin() * self.orbit_angle(time).sin()).asin()
+    }
+
+    /// Rotation that leans the body's pole by its axial tilt at a given
+    /// frame, without its daily turn
+    pub fn axis(&self, time : f64) -> Matrix3<f64> {
+        // The axis leans towards the sun at the northern solstice and
+        // sideways (relative to the sun) at the equinoxes
+        let up = Vector3::unit_y();
+        let s = sun();
+        let side = s.cross(up).normalize();
+        let a = self.orbit_angle(time);
+        let lean = s * a.sin() + side * a.cos();
+
+        Matrix3::from_axis_angle(up.cross(lean).normalize(), Deg(self.axial_tilt))
+    }
+
+    /// Angle turned about the pole by a given frame, in radians
+    pub fn turn(&self, time : f64) -> f64 {
+        2.0 * PI * (time / self.day).fract()
+    }
+
+    /// Rotation from the body's frame into view space at a given frame
+    pub fn orientation(&self, time : f64) -> Matrix3<f64> {
+        self.axis(time) * Matrix3::from_angle_y(Rad(self.turn(time)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use world::sphere::from_lat_lon;
+
+    #[test]
+    fn turn_brings_its_longitude_to_the_viewer() {
+        // The flat map centers its hemisphere view on this longitude
+        let spin = Spin { axial_tilt : 0.0, day : 300.0, .. Spin::default() };
+        for &t in [0.0, 50.0, 220.0, 1000.0].iter() {
+            let facing = spin.orientation(t) * from_lat_lon(0.0, spin.turn(t) - PI / 2.0);
+            assert!((facing - Vector3::new(0.0, 0.0, -1.0)).magnitude() < 1e-9, "{}", t);
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use std::error::Error;
+
+use world::noise_graph::{NoiseGraph, Fractal};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Parameters for a planetary ring system, which lies near the planet's
+/// equatorial plane
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Rings {
+    /// Inner and outer radius, relative to the planet's radius
+    pub inner : f64,
+    pub outer : f64,
+    pub color : [f32; 3],
+    /// Inclination of the ring plane to the planet's equator, in degrees
+    pub tilt : f64,
+
+    /// Density bands, sampled along the X axis at the ring radius
+    pub density : NoiseGraph,
+    /// Empty gaps, as `[inner, outer]` radius pairs
+    pub gaps : Vec<[f64; 2]>,
+}
+
+impl Default for Rings {
+    fn default() -> Rings {
+        Rings {
+            inner : 1.3,
+            outer : 2.2,
+            color : [0.85, 0.8, 0.7],
+            tilt : 0.0,
+            density : NoiseGraph::Fbm(Fractal {
+                seed : 600, octaves : 4, frequency : 12.0, .. Default::default() }),
+            gaps : vec![[1.75, 1.8]],
+        }
+    }
+}
+
+impl Rings {
+    /// Returns `n` density samples in [0, 1], from the inner to the
+    /// outer edge of the rings (a single sample is taken at the inner edge)
+    pub fn profile(&self, n : usize) -> Result<Vec<f32>, Box<Error>> {
+        let density = self.density.compile()?;
+        let step = (self.outer - self.inner) / (n.max(2) - 1) as f64;
+        Ok((0..n).map(|i| {
+            let r = self.inner + step * i as f64;
+            if self.gaps.iter().any(|g| { g[0] <= r && r <= g[1] }) {
+                0.0
+            } else {
+                ((density.get([r, 0.0, 0.0]) + 1.0) / 2.0).max(0.0).min(1.0) as f32
+            }
+        }).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profile_sizes() {
+        let rings = Rings::default();
+        assert!(rings.profile(0).unwrap().is_empty());
+        assert_eq!(rings.profile(1).unwrap().len(), 1);
+
+        let p = rings.profile(64).unwrap();
+        assert_eq!(p.len(), 64);
+        assert!(p.iter().all(|d| { *d >= 0.0 && *d <= 1.0 }));
+    }
+}
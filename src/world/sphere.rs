@@ -0,0 +1,161 @@
+extern crate cgmath;
+
+use std::f64::consts::PI;
+
+use self::cgmath::{Vector3, InnerSpace};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Returns the latitude of a point, in radians, with Y as the pole axis
+pub fn latitude(p : Vector3<f64>) -> f64 {
+    (p.y / p.magnitude()).asin()
+}
+
+/// Returns the longitude of a point, in radians, in the range [-pi, pi]
+pub fn longitude(p : Vector3<f64>) -> f64 {
+    p.z.atan2(p.x)
+}
+
+/// Returns the unit vector at the given latitude and longitude
+pub fn from_lat_lon(lat : f64, lon : f64) -> Vector3<f64> {
+    Vector3::new(lat.cos() * lon.cos(), lat.sin(), lat.cos() * lon.sin())
+}
+
+/// Returns the unit vector at the center of a pixel in an
+/// equirectangular map, with north at the top row
+pub fn equirect(x : u32, y : u32, width : u32, height : u32) -> Vector3<f64> {
+    let lon = ((x as f64 + 0.5) / width as f64) * 2.0 * PI - PI;
+    let lat = PI / 2.0 - ((y as f64 + 0.5) / height as f64) * PI;
+    from_lat_lon(lat, lon)
+}
+
+/// Area of the spherical triangle between three unit vectors, using
+/// Van Oosterom and Strackee's formula for the solid angle
+pub fn triangle_area(a : Vector3<f64>, b : Vector3<f64>, c : Vector3<f64>) -> f64 {
+    let num = a.dot(b.cross(c)).abs();
+    let den = 1.0 + a.dot(b) + b.dot(c) + c.dot(a);
+    2.0 * num.atan2(den)
+}
+
+/// Unit vector pointing east at a given point, tangent to the sphere
+pub fn east(p : Vector3<f64>) -> Vector3<f64> {
+    let e = Vector3::new(-p.z, 0.0, p.x);
+    if e.magnitude2() > 0.0 { e.normalize() } else { Vector3::new(1.0, 0.0, 0.0) }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Nearest-vertex lookup, bucketing vertices by latitude and longitude
+pub struct Nearest<'a> {
+    verts : &'a [Vector3<f64>],
+    buckets : Vec<Vec<usize>>,
+    width : usize,
+    height : usize,
+}
+
+impl<'a> Nearest<'a> {
+    pub fn new(verts : &'a [Vector3<f64>]) -> Nearest<'a> {
+        // Aim for a handful of vertices per bucket
+        let height = ((verts.len() as f64 / 8.0).sqrt() as usize).max(1);
+        let width = height * 2;
+        let mut n = Nearest { verts : verts, buckets : vec![Vec::new(); width * height],
+                              width : width, height : height };
+        for (i, v) in verts.iter().enumerate() {
+            let b = n.bucket(*v);
+            n.buckets[b.1 * width + b.0].push(i);
+        }
+        n
+    }
+
+    fn bucket(&self, p : Vector3<f64>) -> (usize, usize) {
+        let x = (longitude(p) + PI) / (2.0 * PI) * self.width as f64;
+        let y = (latitude(p) + PI / 2.0) / PI * self.height as f64;
+        ((x as usize).min(self.width - 1), (y as usize).min(self.height - 1))
+    }
+
+    /// Returns the index of the vertex closest in direction to `p`
+    pub fn find(&self, p : Vector3<f64>) -> usize {
+        let p = p.normalize();
+        let (bx, by) = self.bucket(p);
+
+        // Grow a ring of buckets until it holds any vertex at all
+        let mut best = None;
+        let mut radius = 0;
+        while best.is_none() && radius <= self.width {
+            let r = radius as i64;
+            for y in (by as i64 - r)..(by as i64 + r + 1) {
+                for x in (bx as i64 - r)..(bx as i64 + r + 1) {
+                    self.search(p, x, y, &mut best);
+                }
+            }
+            radius += 1;
+        }
+        let d = best.expect("Nearest lookup on an empty mesh").1;
+
+        // Buckets are narrower than they are tall away from the equator,
+        // so a closer vertex may be several buckets over.  Search every
+        // bucket that overlaps the cap around `p` reaching the candidate.
+        let angle = d.max(-1.0).min(1.0).acos();
+        let lat = latitude(p);
+        let lon = longitude(p);
+        let dy = PI / self.height as f64;
+        let dx = 2.0 * PI / self.width as f64;
+        let y0 = ((lat - angle + PI / 2.0) / dy).floor() as i64;
+        let y1 = ((lat + angle + PI / 2.0) / dy).floor() as i64;
+        let (x0, x1) = if lat.abs() + angle >= PI / 2.0 {
+            (0, self.width as i64 - 1)
+        } else {
+            let half = (angle.sin() / lat.cos()).min(1.0).asin();
+            (((lon - half + PI) / dx).floor() as i64,
+             ((lon + half + PI) / dx).floor() as i64)
+        };
+        for y in y0..(y1 + 1) {
+            for x in x0..(x1.min(x0 + self.width as i64 - 1) + 1) {
+                self.search(p, x, y, &mut best);
+            }
+        }
+        best.unwrap().0
+    }
+
+    /// Checks the vertices in one bucket, wrapping in longitude and
+    /// skipping rows past the poles
+    fn search(&self, p : Vector3<f64>, x : i64, y : i64, best : &mut Option<(usize, f64)>) {
+        if y < 0 || y >= self.height as i64 {
+            return;
+        }
+        let x = x.rem_euclid(self.width as i64);
+        for &i in &self.buckets[y as usize * self.width + x as usize] {
+            let d = self.verts[i].normalize().dot(p);
+            if best.map(|(_, b)| { d > b }).unwrap_or(true) {
+                *best = Some((i, d));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use draw::icosphere::icosphere;
+
+    #[test]
+    fn nearest_matches_brute_force() {
+        // Nudge the vertices so they don't line up with the buckets
+        let (verts, _) = icosphere(3);
+        let verts : Vec<_> = verts.iter().enumerate()
+            .map(|(i, v)| { v.normalize() + Vector3::new(0.0, 0.0, 0.01)
+                                * ((i * 7919 % 13) as f64 - 6.0) })
+            .collect();
+        let nearest = Nearest::new(&verts);
+        for y in 0..32 {
+            for x in 0..64 {
+                let p = equirect(x, y, 64, 32);
+                let best = verts.iter()
+                    .map(|v| { v.normalize().dot(p) })
+                    .fold(-2.0, f64::max);
+                let found = verts[nearest.find(p)].normalize().dot(p);
+                assert!(found >= best - 1e-12, "wrong vertex near {:?}", p);
+            }
+        }
+    }
+}
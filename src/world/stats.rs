@@ -0,0 +1,145 @@
+use std::cmp::Ordering;
+
+use world::biome::Biomes;
+use world::mesh::Mesh;
+use world::sphere::{latitude, longitude};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// A single point on the surface, with its height above sea level
+#[derive(Clone, Debug, Serialize)]
+pub struct Extreme {
+    pub height : f64,
+    /// Position, in degrees
+    pub latitude : f64,
+    pub longitude : f64,
+}
+
+/// Fraction of the surface in evenly spaced height bins
+#[derive(Clone, Debug, Serialize)]
+pub struct Histogram {
+    /// Height range covered by the bins, relative to sea level
+    pub min : f64,
+    pub max : f64,
+    pub bins : Vec<f64>,
+}
+
+/// Fraction of the surface covered by one biome
+#[derive(Clone, Debug, Serialize)]
+pub struct BiomeArea {
+    pub name : String,
+    pub fraction : f64,
+}
+
+/// Summary of a generated planet.
+///
+/// Heights are in planet radii above sea level, and every fraction is
+/// of spherical surface area rather than vertex count.
+#[derive(Clone, Debug, Serialize)]
+pub struct Stats {
+    pub sea_level : f64,
+    pub land_fraction : f64,
+    pub ocean_fraction : f64,
+    pub histogram : Histogram,
+    /// Biomes in table order, including those with no area
+    pub biomes : Vec<BiomeArea>,
+    pub peak : Extreme,
+    pub trench : Extreme,
+}
+
+impl Stats {
+    pub fn new(mesh : &Mesh, biomes : &Biomes, bins : usize) -> Stats {
+        let areas = mesh.areas();
+        let total : f64 = areas.iter().sum();
+        let heights : Vec<f64> = mesh.heights.iter()
+            .map(|h| { h - mesh.sea_level })
+            .collect();
+
+        let land : f64 = areas.iter().zip(heights.iter())
+            .filter(|&(_, h)| { *h >= 0.0 })
+            .map(|(a, _)| { *a })
+            .sum();
+
+        let extreme = |i : usize| {
+            let p = mesh.verts[i];
+            Extreme { height : heights[i],
+                      latitude : latitude(p).to_degrees(),
+                      longitude : longitude(p).to_degrees() }
+        };
+        // Non-finite heights are left out of the extremes and histogram
+        let finite : Vec<usize> = (0..heights.len())
+            .filter(|i| { heights[*i].is_finite() })
+            .collect();
+        let cmp = |a : &usize, b : &usize| {
+            heights[*a].partial_cmp(&heights[*b]).unwrap_or(Ordering::Equal) };
+        let peak = finite.iter().cloned().max_by(&cmp).unwrap_or(0);
+        let trench = finite.iter().cloned().min_by(&cmp).unwrap_or(0);
+
+        let (min, max) = (heights[trench], heights[peak]);
+        let bins = bins.max(1);
+        let mut histogram = vec![0.0; bins];
+        for (a, h) in areas.iter().zip(heights.iter()) {
+            if !h.is_finite() {
+                continue;
+            }
+            let i = if max > min { ((h - min) / (max - min) * bins as f64) as usize }
+                    else { 0 };
+            histogram[i.min(bins - 1)] += a / total;
+        }
+
+        let mut biome_area = vec![0.0; biomes.table.len()];
+        for (a, b) in areas.iter().zip(mesh.biomes.iter()) {
+            biome_area[*b] += a / total;
+        }
+
+        Stats {
+            sea_level : mesh.sea_level,
+            land_fraction : land / total,
+            ocean_fraction : 1.0 - land / total,
+            histogram : Histogram { min : min, max : max, bins : histogram },
+            biomes : biomes.table.iter().zip(biome_area.into_iter())
+                .map(|(b, f)| { BiomeArea { name : b.name.clone(), fraction : f } })
+                .collect(),
+            peak : extreme(peak),
+            trench : extreme(trench),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::Config;
+
+    #[test]
+    fn extremes_and_histogram_match_the_mesh() {
+        let config = Config { level : 4, .. Config::default() };
+        let mesh = Mesh::generate(&config);
+        let stats = Stats::new(&mesh, &config.biomes, 16);
+
+        let above : Vec<f64> = mesh.heights.iter().map(|h| { h - mesh.sea_level }).collect();
+        let max = above.iter().cloned().fold(::std::f64::MIN, f64::max);
+        let min = above.iter().cloned().fold(::std::f64::MAX, f64::min);
+        assert_eq!(stats.peak.height, max);
+        assert_eq!(stats.trench.height, min);
+        assert_eq!((stats.histogram.min, stats.histogram.max), (min, max));
+
+        assert_eq!(stats.histogram.bins.len(), 16);
+        let total : f64 = stats.histogram.bins.iter().sum();
+        assert!((total - 1.0).abs() < 1e-9, "bins sum to {}", total);
+        let biomes : f64 = stats.biomes.iter().map(|b| { b.fraction }).sum();
+        assert!((biomes - 1.0).abs() < 1e-9);
+        assert!((stats.land_fraction + stats.ocean_fraction - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn nan_heights_are_skipped() {
+        let config = Config { level : 3, .. Config::default() };
+        let mut mesh = Mesh::generate(&config);
+        mesh.heights[0] = ::std::f64::NAN;
+        let stats = Stats::new(&mesh, &config.biomes, 8);
+        assert!(stats.peak.height.is_finite() && stats.trench.height.is_finite());
+        let total : f64 = stats.histogram.bins.iter().sum();
+        assert!(total < 1.0);
+    }
+}
@@ -0,0 +1,198 @@
+extern crate cgmath;
+extern crate image;
+extern crate noise;
+extern crate rand;
+
+use std::collections::VecDeque;
+use std::error::Error;
+
+use world::mesh::Mesh;
+use world::sphere::{equirect, Nearest};
+
+use self::cgmath::{Vector3, InnerSpace};
+use self::image::ImageBuffer;
+use self::noise::{NoiseFn, Perlin, Seedable};
+use self::rand::distributions::{Range, Sample};
+use self::rand::{SeedableRng, ChaChaRng};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Parameters for tectonic plate generation
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TectonicParams {
+    pub seed : u32,
+    pub plates : usize,
+    /// Fraction of plates that carry continental crust
+    pub continental : f64,
+    /// How far boundary features spread, in radians
+    pub width : f64,
+
+    /// Base height of continental and (negative) oceanic plates
+    pub continent_height : f64,
+    pub ocean_depth : f64,
+
+    /// Feature heights at convergent and divergent boundaries
+    pub mountains : f64,
+    pub island_arcs : f64,
+    pub trenches : f64,
+    pub ridges : f64,
+    pub rifts : f64,
+
+    /// Blend between the noise height field (0) and plate elevation (1)
+    pub blend : f64,
+}
+
+impl Default for TectonicParams {
+    fn default() -> TectonicParams {
+        TectonicParams {
+            seed : 0,
+            plates : 12,
+            continental : 0.4,
+            width : 0.08,
+            continent_height : 0.02,
+            ocean_depth : 0.04,
+            mountains : 0.08,
+            island_arcs : 0.04,
+            trenches : 0.05,
+            ridges : 0.015,
+            rifts : 0.02,
+            blend : 0.6,
+        }
+    }
+}
+
+/// Plate assignment and elevation at each mesh vertex
+pub struct Tectonics {
+    pub plate : Vec<usize>,
+    pub elevation : Vec<f64>,
+}
+
+impl Tectonics {
+    /// Builds plates on the mesh, which must still be a unit sphere
+    pub fn generate(mesh : &Mesh, params : &TectonicParams) -> Tectonics {
+        let seed : &[_] = &[params.seed];
+        let mut rng : ChaChaRng = SeedableRng::from_seed(seed);
+        let mut between = Range::new(-1.0, 1.0);
+        let mut unit = || {
+            let mut v = Vector3::new(1.0, 1.0, 1.0);
+            while v.magnitude() > 1.0 || v.magnitude() < 1e-3 {
+                v = Vector3::new(between.sample(&mut rng),
+                                 between.sample(&mut rng),
+                                 between.sample(&mut rng));
+            }
+            v.normalize()
+        };
+
+        // Each plate has a center, a rotation about an Euler pole
+        // (giving a velocity of axis x p), and a crust type.
+        let count = params.plates.max(1);
+        let centers : Vec<Vector3<f64>> = (0..count).map(|_| { unit() }).collect();
+        let axes : Vec<Vector3<f64>> = (0..count).map(|_| { unit() }).collect();
+        let continental : Vec<bool> = (0..count)
+            .map(|i| { (i as f64) < params.continental * count as f64 })
+            .collect();
+
+        // Assign vertices to the nearest plate center, with warped
+        // positions so that plate boundaries wiggle.
+        let warp = [Perlin::new().set_seed(params.seed.wrapping_add(1)),
+                    Perlin::new().set_seed(params.seed.wrapping_add(2)),
+                    Perlin::new().set_seed(params.seed.wrapping_add(3))];
+        let plate : Vec<usize> = mesh.verts.iter().map(|p| {
+            let q = [p.x * 3.0, p.y * 3.0, p.z * 3.0];
+            let w = *p + Vector3::new(warp[0].get(q), warp[1].get(q), warp[2].get(q)) * 0.2;
+            (0..count).max_by(|a, b| {
+                centers[*a].dot(w).partial_cmp(&centers[*b].dot(w)).unwrap()
+            }).unwrap()
+        }).collect();
+
+        // Classify boundary vertices by how fast the plates on either
+        // side are moving towards each other.
+        let neighbors = mesh.neighbors();
+        let mut feature = vec![None; mesh.verts.len()];
+        for i in 0..mesh.verts.len() {
+            let pi = plate[i];
+            let others : Vec<usize> = neighbors[i].iter().cloned()
+                .filter(|j| { plate[*j] != pi })
+                .collect();
+            if others.is_empty() {
+                continue;
+            }
+
+            let mut total = 0.0;
+            for &j in &others {
+                let p = mesh.verts[i];
+                let rel = axes[pi].cross(p) - axes[plate[j]].cross(p);
+                total += rel.dot((mesh.verts[j] - p).normalize());
+            }
+            let convergence = (total / others.len() as f64).max(-1.0).min(1.0);
+            let pj = plate[others[0]];
+
+            let h = if convergence > 0.0 {
+                convergence * match (continental[pi], continental[pj]) {
+                    (true, _) => params.mountains,
+                    (false, true) => -params.trenches,
+                    // Where two oceanic plates meet, one subducts under
+                    // the other, leaving a trench beside an island arc
+                    (false, false) => if pi < pj { -params.trenches }
+                                      else { params.island_arcs },
+                }
+            } else {
+                -convergence * if continental[pi] { -params.rifts }
+                               else { params.ridges }
+            };
+            feature[i] = Some(h);
+        }
+
+        // Spread boundary features inland with a breadth-first search,
+        // fading them with distance from the boundary.
+        let edge = mesh.tris.first().map(|t| {
+            (mesh.verts[t[0]] - mesh.verts[t[1]]).magnitude() }).unwrap_or(1.0);
+        let mut nearest : Vec<Option<(f64, usize)>> = feature.iter()
+            .map(|f| { f.map(|f| { (f, 0) }) })
+            .collect();
+        let mut queue : VecDeque<usize> = (0..mesh.verts.len())
+            .filter(|i| { feature[*i].is_some() })
+            .collect();
+        let max_hops = (params.width * 3.0 / edge).ceil() as usize;
+        while let Some(i) = queue.pop_front() {
+            let (f, hops) = nearest[i].unwrap();
+            if hops >= max_hops {
+                continue;
+            }
+            for &j in &neighbors[i] {
+                if nearest[j].is_none() && plate[j] == plate[i] {
+                    nearest[j] = Some((f, hops + 1));
+                    queue.push_back(j);
+                }
+            }
+        }
+
+        let elevation = (0..mesh.verts.len()).map(|i| {
+            let base = if continental[plate[i]] { params.continent_height }
+                       else { -params.ocean_depth };
+            base + nearest[i].map(|(f, hops)| {
+                let d = hops as f64 * edge / params.width;
+                f * (-d * d).exp()
+            }).unwrap_or(0.0)
+        }).collect();
+
+        Tectonics { plate : plate, elevation : elevation }
+    }
+
+    /// Saves an equirectangular map with one random color per plate
+    pub fn save_plate_map(&self, mesh : &Mesh, path : &str, width : u32)
+        -> Result<(), Box<Error>>
+    {
+        let height = width / 2;
+        let nearest = Nearest::new(&mesh.verts);
+        let img = ImageBuffer::from_fn(width, height, |x, y| {
+            let p = self.plate[nearest.find(equirect(x, y, width, height))];
+            // Cheap integer hash to spread plate ids across colors
+            let h = (p as u32 + 1).wrapping_mul(2654435761);
+            image::Rgb([(h >> 24) as u8, (h >> 16) as u8, (h >> 8) as u8])
+        });
+        img.save(path)?;
+        Ok(())
+    }
+}